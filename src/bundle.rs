@@ -0,0 +1,344 @@
+use crate::{
+    git_ops::{set_origin_refs, update_bare_mirror},
+    network::NetworkOptions,
+    remote::UrlResolver,
+    repo_url::RepoUrl,
+    vcs::{Repo, ReposFile},
+};
+use color_eyre::{
+    eyre::{bail, ensure, eyre, Context},
+    Result,
+};
+use git2::{Buf, ObjectType, Oid, Repository};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// The first line of a version 2 Git bundle.
+const BUNDLE_SIGNATURE: &str = "# v2 git bundle\n";
+
+/// The manifest written next to the bundles.
+pub const MANIFEST_FILE: &str = "manifest.yaml";
+
+/// The list of bundles in a bundle directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub repositories: IndexMap<PathBuf, BundleEntry>,
+}
+
+/// A bundled repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// The upstream URL, recorded in `.gitmodules` on import.
+    pub url: RepoUrl,
+    pub version: String,
+    /// The commit the version pointed to when the bundle was created.
+    pub commit: String,
+    /// The bundle file, relative to the manifest.
+    pub bundle: PathBuf,
+}
+
+/// A directory of bundles created by `vcs2git bundle create`
+#[derive(Debug)]
+pub struct BundleDir {
+    dir: PathBuf,
+    manifest: BundleManifest,
+}
+
+impl BundleDir {
+    /// Read the manifest of a bundle directory.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let reader = BufReader::new(
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?,
+        );
+        let manifest = serde_yaml::from_reader(reader)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// Find the bundle holding the version of a repository.
+    fn find(&self, repo: &Repo) -> Option<&BundleEntry> {
+        let url = repo.url.normalized();
+        self.manifest
+            .repositories
+            .values()
+            .find(|entry| entry.url.normalized() == url && entry.version == repo.version)
+    }
+
+    /// Check that every repository has a bundle of its version.
+    /// Returns a description of each problem found.
+    pub fn check<'a, I>(&self, repos: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+    {
+        repos
+            .into_iter()
+            .filter(|(_, repo)| self.find(repo).is_none())
+            .map(|(path, repo)| {
+                format!(
+                    "{}: no bundle of {} at version '{}'",
+                    path.display(),
+                    repo.url,
+                    repo.version
+                )
+            })
+            .collect()
+    }
+
+    /// Import the bundle of a repository into a submodule repository.
+    pub fn import(&self, repo: &Repository, entry: &Repo) -> Result<()> {
+        let bundle = self
+            .find(entry)
+            .ok_or_else(|| eyre!("No bundle of {} at version '{}'", entry.url, entry.version))?;
+        unbundle(repo, &self.dir.join(&bundle.bundle))
+    }
+}
+
+/// Create one bundle per repository of a repos file, containing the
+/// listed version, and a manifest describing them.
+pub fn create_bundles(
+    repos_file: &ReposFile,
+    resolver: &UrlResolver,
+    network: &NetworkOptions,
+    out_dir: &Path,
+) -> Result<BundleManifest> {
+    fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+    // Repositories are downloaded to a scratch directory first, which
+    // is removed again whether bundling succeeds or not
+    let work_dir = out_dir.join(".vcs2git-work");
+    remove_work_dir(&work_dir)?;
+    let result = write_bundles(repos_file, resolver, network, out_dir, &work_dir);
+    let removed = remove_work_dir(&work_dir);
+    let manifest = result?;
+    removed?;
+
+    let manifest_path = out_dir.join(MANIFEST_FILE);
+    fs::write(&manifest_path, serde_yaml::to_string(&manifest)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    Ok(manifest)
+}
+
+/// Write the bundles of [`create_bundles`], downloading to `work_dir`.
+fn write_bundles(
+    repos_file: &ReposFile,
+    resolver: &UrlResolver,
+    network: &NetworkOptions,
+    out_dir: &Path,
+    work_dir: &Path,
+) -> Result<BundleManifest> {
+    let mut manifest = BundleManifest::default();
+
+    for (index, (path, repo)) in repos_file.repositories.iter().enumerate() {
+        info!("Bundling {} at {}", repo.url, repo.version);
+
+        let scratch_path = work_dir.join(format!("{index}.git"));
+        let candidates = resolver.resolve(repo)?.candidates();
+        update_bare_mirror(&scratch_path, &candidates, network)
+            .with_context(|| format!("Failed to fetch {}", repo.url))?;
+
+        let scratch = Repository::open_bare(&scratch_path)?;
+        let (ref_name, target) = find_version(&scratch, &repo.version)
+            .with_context(|| format!("Version '{}' not found in {}", repo.version, repo.url))?;
+        let commit = scratch.find_object(target, None)?.peel_to_commit()?.id();
+
+        // The index keeps paths such as `a/b` and `a-b` apart
+        let bundle = PathBuf::from(format!(
+            "{index}-{}.bundle",
+            path.to_string_lossy().replace('/', "-")
+        ));
+        write_bundle(&scratch, &[(ref_name, target)], &out_dir.join(&bundle))?;
+
+        manifest.repositories.insert(
+            path.clone(),
+            BundleEntry {
+                url: repo.url.clone(),
+                version: repo.version.clone(),
+                commit: commit.to_string(),
+                bundle,
+            },
+        );
+    }
+
+    Ok(manifest)
+}
+
+/// Remove the scratch directory of [`create_bundles`], also the one
+/// left by a run that was killed.
+fn remove_work_dir(work_dir: &Path) -> Result<()> {
+    if work_dir.exists() {
+        fs::remove_dir_all(work_dir)
+            .with_context(|| format!("Failed to remove {}", work_dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Find the ref to bundle for a version: the tag or branch of that
+/// name, or `HEAD` for a commit hash.
+fn find_version(repo: &Repository, version: &str) -> Result<(String, Oid)> {
+    for name in [
+        format!("refs/tags/{version}"),
+        format!("refs/heads/{version}"),
+    ] {
+        if let Ok(reference) = repo.find_reference(&name) {
+            let target = reference
+                .target()
+                .ok_or_else(|| eyre!("{name} is a symbolic reference"))?;
+            return Ok((name, target));
+        }
+    }
+
+    let commit = repo.revparse_single(version)?.peel_to_commit()?;
+    Ok(("HEAD".to_string(), commit.id()))
+}
+
+/// Write a Git bundle holding the given refs and everything reachable
+/// from them.
+///
+/// The file can also be read by `git clone` and `git fetch`.
+pub fn write_bundle(repo: &Repository, refs: &[(String, Oid)], path: &Path) -> Result<()> {
+    let mut builder = repo.packbuilder()?;
+    let mut walk = repo.revwalk()?;
+
+    for (_, target) in refs {
+        let object = repo.find_object(*target, None)?;
+        if object.kind() == Some(ObjectType::Tag) {
+            builder.insert_object(*target, None)?;
+        }
+        walk.push(object.peel_to_commit()?.id())?;
+    }
+    builder.insert_walk(&mut walk)?;
+
+    let mut pack = Buf::new();
+    builder.write_buf(&mut pack)?;
+
+    let mut file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(BUNDLE_SIGNATURE.as_bytes())?;
+    for (name, target) in refs {
+        writeln!(file, "{target} {name}")?;
+    }
+    writeln!(file)?;
+    file.write_all(&pack)?;
+
+    Ok(())
+}
+
+/// Store the objects of a Git bundle in a repository and create its
+/// refs as if they had been fetched from origin.
+///
+/// The pack data is streamed into the object database.
+pub fn unbundle(repo: &Repository, path: &Path) -> Result<()> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    let refs = read_bundle_header(&mut reader)
+        .with_context(|| format!("Failed to parse bundle {}", path.display()))?;
+
+    let odb = repo.odb()?;
+    let mut writer = odb.packwriter()?;
+    let copied = io::copy(&mut reader, &mut writer)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    ensure!(copied > 0, "The bundle {} has no pack data", path.display());
+    writer.commit()?;
+
+    let refs = refs.iter().map(|(name, target)| (name.as_str(), *target));
+    set_origin_refs(repo, refs, "vcs2git: import bundle")
+}
+
+/// The refs listed in a bundle header, by name.
+type BundleRefs = Vec<(String, Oid)>;
+
+/// Read the header of a bundle up to its pack data, and return the
+/// refs listed in it.
+fn read_bundle_header(reader: &mut impl BufRead) -> Result<BundleRefs> {
+    let mut read_line = || -> Result<String> {
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        ensure!(line.pop() == Some(b'\n'), "The bundle header is truncated");
+        Ok(String::from_utf8(line)?)
+    };
+
+    ensure!(
+        read_line().ok().as_deref() == BUNDLE_SIGNATURE.strip_suffix('\n'),
+        "Not a version 2 Git bundle"
+    );
+    let mut refs = Vec::new();
+
+    loop {
+        let line = read_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with('-') {
+            bail!("Bundles with prerequisite commits are not supported");
+        }
+
+        let (oid, name) = line
+            .split_once(' ')
+            .ok_or_else(|| eyre!("Invalid bundle header line '{line}'"))?;
+        refs.push((name.to_string(), Oid::from_str(oid)?));
+    }
+
+    Ok(refs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = Repository::init(temp_dir.path().join("source")).unwrap();
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree_id = source.index().unwrap().write_tree().unwrap();
+        let tree = source.find_tree(tree_id).unwrap();
+        let commit = source
+            .commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])
+            .unwrap();
+        let tag = source
+            .tag(
+                "1.0.0",
+                source.find_commit(commit).unwrap().as_object(),
+                &sig,
+                "Release",
+                false,
+            )
+            .unwrap();
+
+        let path = temp_dir.path().join("repo.bundle");
+        let refs = [
+            ("refs/heads/main".to_string(), commit),
+            ("refs/tags/1.0.0".to_string(), tag),
+        ];
+        write_bundle(&source, &refs, &path).unwrap();
+
+        let target = Repository::init(temp_dir.path().join("target")).unwrap();
+        unbundle(&target, &path).unwrap();
+
+        let branch = target.find_reference("refs/remotes/origin/main").unwrap();
+        assert_eq!(branch.target(), Some(commit));
+        assert!(target.find_object(tag, None).is_ok());
+        assert!(target.find_tree(tree_id).is_ok());
+    }
+
+    #[test]
+    fn test_read_bundle_header_rejects_prerequisites() {
+        let content = format!("{BUNDLE_SIGNATURE}-{} parent\n\nPACK", Oid::zero());
+        assert!(read_bundle_header(&mut content.as_bytes()).is_err());
+        assert!(read_bundle_header(&mut b"not a bundle".as_slice()).is_err());
+    }
+}
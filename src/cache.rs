@@ -0,0 +1,167 @@
+use crate::{
+    git_ops::{set_origin_refs, update_bare_mirror},
+    network::NetworkOptions,
+    repo_url::RepoUrl,
+};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use git2::{ObjectType, Oid, Repository};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// A directory of bare repositories shared between runs, whose objects
+/// are borrowed by submodules instead of being downloaded again
+#[derive(Debug, Clone)]
+pub struct ObjectCache {
+    dir: PathBuf,
+    dissociate: bool,
+    /// Cache repositories already updated in this run, with the index
+    /// of the URL they were fetched from, or `None` if updating failed.
+    updated: RefCell<HashMap<PathBuf, Option<usize>>>,
+}
+
+impl ObjectCache {
+    pub fn new(dir: PathBuf, dissociate: bool) -> Self {
+        Self {
+            dir,
+            dissociate,
+            updated: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The cache directory used when none is given:
+    /// `$XDG_CACHE_HOME/vcs2git` or `~/.cache/vcs2git`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache_home.join("vcs2git"))
+    }
+
+    /// The location of the cached copy of a repository.
+    ///
+    /// Different spellings of the same URL share one copy.
+    pub fn repo_path(&self, url: &RepoUrl) -> PathBuf {
+        let hash = Oid::hash_object(ObjectType::Blob, url.normalized().as_bytes())
+            .expect("hashing a buffer cannot fail");
+        self.dir.join(format!("{hash}.git"))
+    }
+
+    /// Update the cached copy of a repository and make it available in
+    /// a submodule repository. Returns the index of the URL the cache
+    /// was fetched from.
+    pub fn prepare(
+        &self,
+        repo: &Repository,
+        urls: &[RepoUrl],
+        network: &NetworkOptions,
+    ) -> Result<usize> {
+        let (path, used) = self.update(urls, network)?;
+        self.seed(&path, repo)?;
+        Ok(used)
+    }
+
+    /// Create or update the cached copy of a repository, fetching from
+    /// the first URL that works. Each copy is updated at most once per
+    /// run, and not tried again after it failed.
+    pub fn update(&self, urls: &[RepoUrl], network: &NetworkOptions) -> Result<(PathBuf, usize)> {
+        let primary = urls.first().ok_or_else(|| eyre!("No URL to cache"))?;
+        let path = self.repo_path(primary);
+
+        match self.updated.borrow().get(&path) {
+            Some(Some(used)) => return Ok((path, *used)),
+            Some(None) => bail!("Updating the cached copy of {primary} failed earlier"),
+            None => {}
+        }
+
+        info!("Updating cached copy of {primary}");
+        let result = update_bare_mirror(&path, urls, network);
+        self.updated
+            .borrow_mut()
+            .insert(path.clone(), result.as_ref().ok().copied());
+
+        Ok((path, result?))
+    }
+
+    /// Make the objects and refs of a cached repository available in a
+    /// submodule repository.
+    ///
+    /// The cached branches appear as `origin/*` remote-tracking
+    /// branches. Unless dissociating, the objects are referenced
+    /// through `objects/info/alternates` instead of being copied.
+    pub fn seed(&self, cache_path: &Path, repo: &Repository) -> Result<()> {
+        let cache_url = cache_path
+            .to_str()
+            .ok_or_else(|| eyre!("Cache path {} is not UTF-8", cache_path.display()))?;
+        let refspecs = [
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ];
+
+        if self.dissociate {
+            // Copy the objects through a local fetch
+            let mut remote = repo.remote_anonymous(cache_url)?;
+            remote.fetch(&refspecs, None, None)?;
+            return Ok(());
+        }
+
+        let objects_dir = cache_path.join("objects").canonicalize()?;
+        add_alternate(repo, &objects_dir)?;
+
+        let cache = Repository::open_bare(cache_path)?;
+        let references: Vec<_> = cache.references()?.collect::<Result<_, _>>()?;
+        let refs = references
+            .iter()
+            .filter_map(|reference| Some((reference.name()?, reference.target()?)));
+        set_origin_refs(repo, refs, "vcs2git: seed from cache")
+    }
+}
+
+/// Register an object directory as an alternate of the repository,
+/// both on disk and in the opened object database.
+fn add_alternate(repo: &Repository, objects_dir: &Path) -> Result<()> {
+    let alternates_path = repo.path().join("objects/info/alternates");
+    let line = objects_dir.to_string_lossy();
+
+    let existing = fs::read_to_string(&alternates_path).unwrap_or_default();
+    if !existing.lines().any(|existing| existing == line) {
+        if let Some(parent) = alternates_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&alternates_path)?;
+        writeln!(file, "{line}")?;
+    }
+
+    repo.odb()?.add_disk_alternate(&line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_path_shared_by_spellings() {
+        let cache = ObjectCache::new(PathBuf::from("/cache"), false);
+        let https: RepoUrl = "https://github.com/org/repo.git".parse().unwrap();
+        let scp: RepoUrl = "git@github.com:org/repo".parse().unwrap();
+        let other: RepoUrl = "https://github.com/org/other.git".parse().unwrap();
+
+        assert_eq!(cache.repo_path(&https), cache.repo_path(&scp));
+        assert_ne!(cache.repo_path(&https), cache.repo_path(&other));
+        assert!(cache.repo_path(&https).starts_with("/cache"));
+    }
+}
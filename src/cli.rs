@@ -1,9 +1,331 @@
-use clap::{ArgGroup, Parser};
-use std::path::PathBuf;
+use crate::{
+    config::{RecordUrl, UrlRewrite, UrlStyle},
+    lint::{Rule, Severity},
+};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::{ffi::OsString, path::PathBuf};
+
+/// The parsed command line, either a plain import or an auxiliary
+/// subcommand.
+///
+/// It is parsed once, so the size of the import options does not
+/// matter.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Cli {
+    Import(Opts),
+    Command(Command),
+}
+
+impl Cli {
+    /// Parse the process arguments.
+    pub fn parse() -> Self {
+        Self::parse_from(std::env::args_os())
+    }
+
+    /// Parse the given arguments, exiting with a usage message on error.
+    pub fn parse_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::try_parse_from(args).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Parse the given arguments.
+    ///
+    /// The first argument selects a subcommand if it names one.
+    /// Otherwise the arguments are parsed as an import, so that the
+    /// original `vcs2git <REPO_FILE> <PREFIX>` form keeps working.
+    pub fn try_parse_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let is_subcommand = args
+            .get(1)
+            .and_then(|arg| arg.to_str())
+            .is_some_and(Command::has_subcommand);
+
+        if is_subcommand {
+            Ok(Self::Command(Command::try_parse_from(args)?))
+        } else {
+            Ok(Self::Import(Opts::try_parse_from(args)?))
+        }
+    }
+}
+
+/// Auxiliary subcommands.
+#[derive(Debug, Clone, Parser)]
+#[clap(name = "vcs2git")]
+pub enum Command {
+    /// Check a repos file for common problems without touching the
+    /// network.
+    Lint(LintOpts),
+
+    /// Check that the submodules match a repos file and comply with
+    /// the policy.
+    Verify(VerifyOpts),
+
+    /// Create or update bare mirrors of all listed repositories for
+    /// use with --offline.
+    Mirror(MirrorOpts),
+
+    /// Transfer repositories as Git bundle files.
+    #[clap(subcommand)]
+    Bundle(BundleCommand),
+
+    /// Initialize and check out all submodules of a fresh clone at
+    /// their recorded commits, without a repos file. Shallow and
+    /// sparse checkouts are not supported.
+    Bootstrap(BootstrapOpts),
+
+    /// Continue an import that was interrupted, with the same options.
+    Resume,
+
+    /// Roll back an import that was interrupted.
+    Abort,
+
+    /// Restore the submodules to their state before the last import.
+    Undo(UndoOpts),
+
+    /// List, restore or purge submodules removed by --sync-selection.
+    #[clap(subcommand)]
+    Trash(TrashCommand),
+}
+
+/// Actions of the `bundle` subcommand.
+#[derive(Debug, Clone, Subcommand)]
+pub enum BundleCommand {
+    /// Write one bundle per listed repository and a manifest, for
+    /// import with --from-bundles.
+    Create(BundleCreateOpts),
+}
+
+/// Actions of the `trash` subcommand.
+#[derive(Debug, Clone, Subcommand)]
+pub enum TrashCommand {
+    /// List the removed submodules in the trash.
+    List,
+
+    /// Add a removed submodule again, at the commit it was removed at.
+    Restore(TrashRestoreOpts),
+
+    /// Delete removed submodules from the trash for good.
+    Purge(TrashPurgeOpts),
+}
+
+/// Options of the `trash restore` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct TrashRestoreOpts {
+    /// The ID of the entry, as shown by `trash list`.
+    pub id: String,
+}
+
+/// Options of the `trash purge` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct TrashPurgeOpts {
+    /// The IDs of the entries, as shown by `trash list`.
+    #[clap(required_unless_present = "all")]
+    pub ids: Vec<String>,
+
+    /// Purge all entries.
+    #[clap(long, conflicts_with = "ids")]
+    pub all: bool,
+}
+
+/// Options of the `bundle create` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct BundleCreateOpts {
+    /// The YAML file of a repository list.
+    pub repo_file: PathBuf,
+
+    /// The directory to write the bundles and the manifest to.
+    pub out_dir: PathBuf,
+
+    /// The configuration file (defaults to .vcs2git.toml if present).
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network: NetworkArgs,
+}
+
+/// Options for network operations, shared by all commands that fetch.
+#[derive(Debug, Clone, Default, Args)]
+pub struct NetworkArgs {
+    /// The HTTP proxy for HTTP and HTTPS URLs, overriding http.proxy
+    /// and the proxy environment variables.
+    #[clap(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Contact this host or domain without the proxy, in addition to
+    /// the no_proxy environment variable.
+    #[clap(long, value_name = "HOST")]
+    pub no_proxy: Vec<String>,
+
+    /// Trust the CA certificates in this PEM file in addition to the
+    /// system ones.
+    #[clap(long, value_name = "FILE")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Accept only the SSH host key with this SHA-256 fingerprint, as
+    /// printed by `ssh-keygen -lf`, for HOST.
+    #[clap(long, value_name = "HOST=FINGERPRINT", value_parser = parse_assignment)]
+    pub host_key: Vec<(String, String)>,
+
+    /// Do not verify TLS certificates and SSH host keys. Dangerous.
+    #[clap(long)]
+    pub insecure: bool,
+
+    /// Abort fetching a repository after this many seconds.
+    #[clap(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Use a different fetch timeout for HOST.
+    #[clap(long, value_name = "HOST=SECS", value_parser = parse_host_timeout)]
+    pub host_timeout: Vec<(String, u64)>,
+
+    /// Abort when all network operations together take longer than
+    /// this many seconds.
+    #[clap(long, value_name = "SECS")]
+    pub total_timeout: Option<u64>,
+
+    /// Retry a fetch that failed for a transient reason, such as a
+    /// dropped connection, this many times [default: 2].
+    #[clap(long, value_name = "N")]
+    pub retries: Option<u32>,
+
+    /// Wait this many seconds before the first retry, doubling the wait
+    /// for each further one [default: 1].
+    #[clap(long, value_name = "SECS")]
+    pub retry_delay: Option<u64>,
+}
+
+/// Options of the `lint` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct LintOpts {
+    /// The YAML file of a repository list.
+    pub repo_file: PathBuf,
+
+    /// Report violations of this rule as errors.
+    #[clap(long, value_name = "RULE")]
+    pub deny: Vec<Rule>,
+
+    /// Report violations of this rule as warnings.
+    #[clap(long, value_name = "RULE")]
+    pub warn: Vec<Rule>,
+
+    /// Do not report violations of this rule.
+    #[clap(long, value_name = "RULE")]
+    pub allow: Vec<Rule>,
+}
+
+/// Options of the `verify` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct VerifyOpts {
+    /// The YAML file of a repository list.
+    pub repo_file: PathBuf,
+
+    /// The directory the submodules were added to.
+    pub prefix: PathBuf,
+
+    /// The policy file to enforce (defaults to .vcs2git-policy.toml if
+    /// present).
+    #[clap(long, value_name = "FILE")]
+    pub policy: Option<PathBuf>,
+}
+
+/// Options of the `mirror` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct MirrorOpts {
+    /// The YAML file of a repository list.
+    pub repo_file: PathBuf,
+
+    /// The directory to keep the mirrors in.
+    pub dir: PathBuf,
+
+    /// The configuration file (defaults to .vcs2git.toml if present).
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network: NetworkArgs,
+}
+
+/// Options of the `bootstrap` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct BootstrapOpts {
+    /// Only set up the submodules of the superproject, not the ones
+    /// nested in them.
+    #[clap(long)]
+    pub no_recursive: bool,
+
+    /// The configuration file (defaults to .vcs2git.toml if present).
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Keep bare copies of fetched repositories in this directory and
+    /// borrow their objects instead of downloading them again.
+    #[clap(long, value_name = "DIR")]
+    pub reference_dir: Option<PathBuf>,
+
+    /// Use the object cache in ~/.cache/vcs2git (like --reference-dir).
+    #[clap(long)]
+    pub cache: bool,
+
+    /// Copy objects from the object cache instead of referencing them.
+    #[clap(long)]
+    pub dissociate: bool,
+
+    /// Fetch only from the mirrors in --mirror-dir, never from the
+    /// network.
+    #[clap(long, requires = "mirror_dir")]
+    pub offline: bool,
+
+    /// The directory of mirrors created by `vcs2git mirror`.
+    #[clap(long, value_name = "DIR", requires = "offline")]
+    pub mirror_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network: NetworkArgs,
+}
+
+/// Options of the `undo` subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct UndoOpts {
+    /// Undo the import even if commits were made since.
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl LintOpts {
+    /// Get the severity overrides given on the command line.
+    pub fn severity_overrides(&self) -> Vec<(Rule, Severity)> {
+        let deny = self.deny.iter().map(|&rule| (rule, Severity::Error));
+        let warn = self.warn.iter().map(|&rule| (rule, Severity::Warning));
+        let allow = self.allow.iter().map(|&rule| (rule, Severity::Allow));
+        allow.chain(warn).chain(deny).collect()
+    }
+}
 
 /// It reads a VCS repos file and add listed repositories as Git
 /// submodules.
 #[derive(Debug, Clone, Parser)]
+#[clap(
+    name = "vcs2git",
+    after_help = "Subcommands:\n  \
+        lint       Check a repos file for common problems\n  \
+        verify     Check that the submodules match a repos file\n  \
+        mirror     Create or update bare mirrors of the listed repositories\n  \
+        bundle     Transfer repositories as Git bundle files\n  \
+        bootstrap  Set up all submodules of a fresh clone\n  \
+        resume     Continue an interrupted import\n  \
+        abort      Roll back an interrupted import\n  \
+        undo       Undo the last import\n  \
+        trash      List, restore or purge removed submodules"
+)]
 #[clap(group(
     ArgGroup::new("selection")
         .args(&["only"])
@@ -36,12 +358,132 @@ pub struct Opts {
     #[clap(long)]
     pub sync_selection: bool,
 
+    /// Also keep the working directories of removed submodules in the
+    /// trash, not only their repositories.
+    #[clap(long)]
+    pub trash_worktree: bool,
+
+    /// Refuse to update submodules to commits that are not descendants
+    /// of their current commits.
+    #[clap(long)]
+    pub ff_only: bool,
+
+    /// Remove or update submodules even if they have unpushed commits,
+    /// local branches or stashes.
+    #[clap(long)]
+    pub force_remove: bool,
+
+    /// Stash local changes in submodules being updated and apply them
+    /// again after the update.
+    #[clap(long, conflicts_with = "force")]
+    pub autostash: bool,
+
+    /// Discard local changes in submodules being updated.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Treat untracked files in submodules as clean.
+    #[clap(long)]
+    pub ignore_untracked: bool,
+
+    /// Initialize and check out submodules that are not initialized
+    /// before the run, instead of refusing to run.
+    #[clap(long)]
+    pub init: bool,
+
+    /// Check the state of all submodules before the run, not only the
+    /// ones it updates or removes.
+    #[clap(long)]
+    pub strict: bool,
+
     /// Preview what would be done without making changes.
     #[clap(long)]
     pub dry_run: bool,
+
+    /// Roll back only the repositories that fail and continue with the
+    /// others, instead of rolling back everything.
+    #[clap(long)]
+    pub keep_going: bool,
+
+    /// The policy file to enforce (defaults to .vcs2git-policy.toml if
+    /// present).
+    #[clap(long, value_name = "FILE")]
+    pub policy: Option<PathBuf>,
+
+    /// The configuration file (defaults to .vcs2git.toml if present).
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Fetch URLs starting with FROM from URLs starting with TO instead.
+    #[clap(long, value_name = "FROM=TO", value_parser = parse_assignment)]
+    pub rewrite_url: Vec<(String, String)>,
+
+    /// Fetch URLs matching PATTERN from REPLACEMENT instead, which may
+    /// refer to capture groups such as `$1`.
+    #[clap(long, value_name = "PATTERN=REPLACEMENT", value_parser = parse_assignment)]
+    pub rewrite_url_regex: Vec<(String, String)>,
+
+    /// Which URL to record in .gitmodules when a URL is rewritten.
+    #[clap(long, value_enum, value_name = "URL")]
+    pub record_url: Option<RecordUrl>,
+
+    /// Convert GitHub, GitLab, Bitbucket and configured forge URLs to
+    /// this style.
+    #[clap(long, value_enum, value_name = "STYLE")]
+    pub url_style: Option<UrlStyle>,
+
+    /// Keep bare copies of fetched repositories in this directory and
+    /// borrow their objects instead of downloading them again.
+    #[clap(long, value_name = "DIR")]
+    pub reference_dir: Option<PathBuf>,
+
+    /// Use the object cache in ~/.cache/vcs2git (like --reference-dir).
+    #[clap(long)]
+    pub cache: bool,
+
+    /// Copy objects from the object cache instead of referencing them,
+    /// so that submodules keep working if the cache is deleted.
+    #[clap(long)]
+    pub dissociate: bool,
+
+    /// Fetch only from the mirrors in --mirror-dir, never from the
+    /// network.
+    #[clap(long, requires = "mirror_dir")]
+    pub offline: bool,
+
+    /// The directory of mirrors created by `vcs2git mirror`.
+    #[clap(long, value_name = "DIR", requires = "offline")]
+    pub mirror_dir: Option<PathBuf>,
+
+    /// Add submodules from the bundles created by `vcs2git bundle
+    /// create` in this directory instead of fetching.
+    #[clap(long, value_name = "DIR", conflicts_with = "offline")]
+    pub from_bundles: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network: NetworkArgs,
 }
 
 impl Opts {
+    /// Get the URL rewrite rules given on the command line
+    pub fn url_rewrites(&self) -> Vec<UrlRewrite> {
+        let prefixes = self
+            .rewrite_url
+            .iter()
+            .map(|(from, to)| UrlRewrite::Prefix {
+                instead_of: from.clone(),
+                base: to.clone(),
+            });
+        let regexes =
+            self.rewrite_url_regex
+                .iter()
+                .map(|(pattern, replacement)| UrlRewrite::Regex {
+                    regex: pattern.clone(),
+                    replacement: replacement.clone(),
+                });
+        prefixes.chain(regexes).collect()
+    }
+
     /// Check if we should update existing submodules
     pub fn should_update(&self) -> bool {
         !self.skip_existing
@@ -58,6 +500,23 @@ impl Opts {
     }
 }
 
+/// Parse a `KEY=VALUE` argument, splitting at the first `=`.
+fn parse_assignment(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{arg}'"))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Parse a `HOST=SECS` argument.
+fn parse_host_timeout(arg: &str) -> Result<(String, u64), String> {
+    let (host, secs) = parse_assignment(arg)?;
+    let secs = secs
+        .parse()
+        .map_err(|_| format!("expected a number of seconds, got '{secs}'"))?;
+    Ok((host, secs))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +573,112 @@ mod tests {
         ]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_rewrite_url_flags() {
+        let opts = Opts::try_parse_from([
+            "vcs2git",
+            "--rewrite-url",
+            "https://github.com/=https://mirror/",
+            "--rewrite-url-regex",
+            "^git@(.*)$=ssh://$1",
+            "--record-url",
+            "rewritten",
+            "test.repos",
+            "src",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            opts.url_rewrites(),
+            vec![
+                UrlRewrite::Prefix {
+                    instead_of: "https://github.com/".to_string(),
+                    base: "https://mirror/".to_string(),
+                },
+                UrlRewrite::Regex {
+                    regex: "^git@(.*)$".to_string(),
+                    replacement: "ssh://$1".to_string(),
+                },
+            ]
+        );
+        assert_eq!(opts.record_url, Some(RecordUrl::Rewritten));
+
+        let result = Opts::try_parse_from(["vcs2git", "--rewrite-url", "nope", "a", "b"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lint_subcommand() {
+        let cli = Cli::try_parse_from([
+            "vcs2git",
+            "lint",
+            "test.repos",
+            "--deny",
+            "floating-version",
+            "--allow",
+            "unknown-key",
+        ])
+        .unwrap();
+
+        let Cli::Command(Command::Lint(opts)) = cli else {
+            panic!("Expected the lint subcommand");
+        };
+        assert_eq!(opts.repo_file, PathBuf::from("test.repos"));
+        assert_eq!(
+            opts.severity_overrides(),
+            vec![
+                (Rule::UnknownKey, Severity::Allow),
+                (Rule::FloatingVersion, Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offline_requires_mirror_dir() {
+        let result = Opts::try_parse_from(["vcs2git", "--offline", "test.repos", "src"]);
+        assert!(result.is_err());
+
+        let opts = Opts::try_parse_from([
+            "vcs2git",
+            "--offline",
+            "--mirror-dir",
+            "/mirror",
+            "test.repos",
+            "src",
+        ])
+        .unwrap();
+        assert!(opts.offline);
+        assert_eq!(opts.mirror_dir, Some(PathBuf::from("/mirror")));
+    }
+
+    #[test]
+    fn test_bundle_create_subcommand() {
+        let cli =
+            Cli::try_parse_from(["vcs2git", "bundle", "create", "test.repos", "out"]).unwrap();
+
+        let Cli::Command(Command::Bundle(BundleCommand::Create(opts))) = cli else {
+            panic!("Expected the bundle create subcommand");
+        };
+        assert_eq!(opts.repo_file, PathBuf::from("test.repos"));
+        assert_eq!(opts.out_dir, PathBuf::from("out"));
+    }
+
+    #[test]
+    fn test_bootstrap_subcommand() {
+        let cli =
+            Cli::try_parse_from(["vcs2git", "bootstrap", "--cache", "--no-recursive"]).unwrap();
+
+        let Cli::Command(Command::Bootstrap(opts)) = cli else {
+            panic!("Expected the bootstrap subcommand");
+        };
+        assert!(opts.cache);
+        assert!(opts.no_recursive);
+    }
+
+    #[test]
+    fn test_import_without_subcommand() {
+        let cli = Cli::try_parse_from(["vcs2git", "test.repos", "src"]).unwrap();
+        assert!(matches!(cli, Cli::Import(_)));
+    }
 }
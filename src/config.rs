@@ -0,0 +1,205 @@
+use crate::repo_url::RepoUrl;
+use clap::ValueEnum;
+use color_eyre::{eyre::Context, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The configuration file looked up in the superproject when
+/// `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = ".vcs2git.toml";
+
+/// Persistent settings read from `.vcs2git.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Rules rewriting repository URLs before fetching.
+    #[serde(default)]
+    pub url_rewrites: Vec<UrlRewrite>,
+
+    /// Which URL to record in `.gitmodules` when a URL is rewritten.
+    #[serde(default)]
+    pub record_url: Option<RecordUrl>,
+
+    /// The preferred spelling of URLs on Git forges.
+    #[serde(default)]
+    pub url_style: Option<UrlStyle>,
+
+    /// Additional hosts treated as Git forges by `url_style`, besides
+    /// GitHub, GitLab and Bitbucket.
+    #[serde(default)]
+    pub forge_hosts: Vec<String>,
+
+    /// URLs tried in order when fetching from the repository URL in the
+    /// key fails, in addition to `fallback_urls` in the repos file.
+    #[serde(default)]
+    pub fallback_urls: IndexMap<String, Vec<RepoUrl>>,
+
+    /// Directory of the shared object cache, enabling the cache.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Settings for network operations.
+    #[serde(default)]
+    pub network: NetworkConfig,
+}
+
+/// The `[network]` table of the configuration file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The HTTP proxy for all HTTP and HTTPS URLs.
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Hosts and domains contacted without the proxy.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+
+    /// A PEM file of CA certificates trusted in addition to the system
+    /// ones.
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// SHA-256 fingerprints of the accepted SSH host keys, by host.
+    /// Hosts not listed are checked against `known_hosts`.
+    #[serde(default)]
+    pub host_keys: IndexMap<String, Vec<String>>,
+
+    /// Seconds allowed for fetching one repository.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    /// Seconds allowed for all network operations of a run.
+    #[serde(default)]
+    pub total_timeout: Option<u64>,
+
+    /// Seconds allowed for fetching one repository, by host.
+    #[serde(default)]
+    pub host_timeouts: IndexMap<String, u64>,
+
+    /// The number of retries after a transient network failure.
+    #[serde(default)]
+    pub retries: Option<u32>,
+
+    /// Seconds to wait before the first retry.
+    #[serde(default)]
+    pub retry_delay: Option<u64>,
+}
+
+/// A URL rewrite rule
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum UrlRewrite {
+    /// Replace the `instead_of` prefix with `base`, like Git's
+    /// `url.<base>.insteadOf`.
+    Prefix { instead_of: String, base: String },
+    /// Replace matches of `regex`, where `replacement` may refer to
+    /// capture groups such as `$1`.
+    Regex { regex: String, replacement: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordUrl {
+    /// Record the URL as written in the repos file.
+    #[default]
+    Original,
+    /// Record the URL after rewriting.
+    Rewritten,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlStyle {
+    /// Convert forge URLs to the scp-like `git@host:path` form.
+    Ssh,
+    /// Convert forge URLs to the `https://host/path` form.
+    Https,
+    /// Leave URLs as written in the repos file.
+    #[default]
+    Keep,
+}
+
+impl Config {
+    /// Load a configuration file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Load the configuration file given on the command line, or the
+    /// default configuration file if it exists.
+    pub fn discover(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => {
+                let path = Path::new(DEFAULT_CONFIG_FILE);
+                if path.exists() {
+                    Self::load(path)
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+record_url = "rewritten"
+
+[fallback_urls]
+"https://github.com/org/a.git" = ["https://mirror.internal/org/a.git"]
+
+[network]
+proxy = "http://proxy.internal:3128"
+no_proxy = [".internal"]
+
+[[url_rewrites]]
+instead_of = "https://github.com/"
+base = "https://gitlab.internal/mirror/"
+
+[[url_rewrites]]
+regex = "^git@github\\.com:(.*)$"
+replacement = "https://gitlab.internal/mirror/$1"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.record_url, Some(RecordUrl::Rewritten));
+        assert_eq!(
+            config.network.proxy.as_deref(),
+            Some("http://proxy.internal:3128")
+        );
+        assert_eq!(config.network.no_proxy, vec![".internal".to_string()]);
+        assert_eq!(
+            config.fallback_urls["https://github.com/org/a.git"],
+            vec!["https://mirror.internal/org/a.git".parse().unwrap()]
+        );
+        assert_eq!(
+            config.url_rewrites,
+            vec![
+                UrlRewrite::Prefix {
+                    instead_of: "https://github.com/".to_string(),
+                    base: "https://gitlab.internal/mirror/".to_string(),
+                },
+                UrlRewrite::Regex {
+                    regex: "^git@github\\.com:(.*)$".to_string(),
+                    replacement: "https://gitlab.internal/mirror/$1".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -1,27 +1,198 @@
+use crate::{interrupt, network::NetworkOptions, repo_url::RepoUrl};
 use color_eyre::{
     eyre::{bail, Context},
     Result,
 };
-use git2::{Cred, ErrorClass, ErrorCode, FetchOptions, RemoteCallbacks, Repository};
+use git2::{
+    build::CheckoutBuilder, Cred, ErrorClass, ErrorCode, FetchOptions, Oid, RemoteCallbacks,
+    Repository, Signature, StashFlags,
+};
 use std::{
-    fs,
+    cell::Cell,
+    ffi::c_int,
+    fmt, fs,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
+use tracing::warn;
+
+/// Refspecs that copy all branches and tags unchanged.
+pub const MIRROR_REFSPECS: &[&str] = &["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
 
 /// Fetch from remote repository
-pub fn fetch(repo: &Repository, remote: &str, version: &str) -> Result<(), git2::Error> {
+pub fn fetch(
+    repo: &Repository,
+    remote: &str,
+    refspecs: &[&str],
+    network: &NetworkOptions,
+) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    let url = remote.url().unwrap_or_default().to_string();
+
+    let timeout = network.fetch_timeout(&url)?;
+    if !network.shared_socket_timeout {
+        set_socket_timeout(timeout)?;
+    }
+    let started = Instant::now();
+    let timed_out = Cell::new(false);
+
     let cb = {
         let mut cb = RemoteCallbacks::new();
         cb.credentials(|_url, username, _allowed_types| {
             Cred::ssh_key_from_agent(username.unwrap())
         });
+        cb.certificate_check(|cert, host| {
+            interrupt::check()?;
+            network.check_certificate(cert, host)
+        });
+        // Returning false aborts the transfer
+        let timed_out = &timed_out;
+        cb.transfer_progress(move |_| {
+            timed_out.set(timeout.is_some_and(|timeout| started.elapsed() > timeout));
+            !timed_out.get() && !interrupt::is_interrupted()
+        });
         cb
     };
     let mut fetch_opts = FetchOptions::new();
     fetch_opts.remote_callbacks(cb);
-    repo.find_remote(remote)?
-        .fetch(&[version], Some(&mut fetch_opts), None)?;
+    fetch_opts.proxy_options(network.proxy_options(&url));
+
+    match remote.fetch(refspecs, Some(&mut fetch_opts), None) {
+        Ok(()) => Ok(()),
+        Err(_) if interrupt::is_interrupted() => Err(interrupt::interrupted_error()),
+        Err(err) if timed_out.get() || err.code() == ErrorCode::Timeout => {
+            let secs = timeout.unwrap_or_default().as_secs_f64().round();
+            Err(git2::Error::new(
+                ErrorCode::Timeout,
+                ErrorClass::Net,
+                format!("Timed out after {secs}s fetching from {url}"),
+            ))
+        }
+        Err(err) => Err(err),
+    }
+}
 
+/// Limit how long connecting to a server and waiting for data from it
+/// may take.
+///
+/// Without a timeout, the longest one is used instead: libgit2 only
+/// waits in a way that signals interrupt when a timeout is set.
+pub fn set_socket_timeout(timeout: Option<Duration>) -> Result<(), git2::Error> {
+    let millis = timeout.map_or(c_int::MAX, |timeout| {
+        timeout.as_millis().clamp(1, c_int::MAX as u128) as c_int
+    });
+    // SAFETY: The setting is only changed from the main thread, while
+    // no fetch runs on another thread. Fetches on worker threads use
+    // the setting made before they were started and leave it alone.
+    unsafe {
+        git2::opts::set_server_connect_timeout_in_milliseconds(millis)?;
+        git2::opts::set_server_timeout_in_milliseconds(millis)?;
+    }
+    Ok(())
+}
+
+/// Fetch from the first of the candidate URLs that works
+///
+/// The remote is pointed at each URL in turn and left pointing at the
+/// first one. Returns the index of the URL that was fetched from.
+pub fn fetch_with_fallbacks(
+    repo: &Repository,
+    remote: &str,
+    urls: &[RepoUrl],
+    refspecs: &[&str],
+    network: &NetworkOptions,
+) -> Result<usize, git2::Error> {
+    let mut last_error = git2::Error::from_str("No URL to fetch from");
+
+    for (index, url) in urls.iter().enumerate() {
+        interrupt::check()?;
+        repo.remote_set_url(remote, url.as_str())?;
+        let result = fetch(repo, remote, refspecs, network);
+        if index > 0 {
+            repo.remote_set_url(remote, urls[0].as_str())?;
+        }
+
+        match result {
+            Ok(()) => return Ok(index),
+            Err(err) => {
+                if index + 1 < urls.len() {
+                    warn!("Failed to fetch from {url}: {err}. Trying the next URL.");
+                }
+                last_error = err;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Create or update a bare repository holding all branches and tags of
+/// the first of the candidate URLs that works. Returns the index of the
+/// URL that was fetched from.
+///
+/// Transient failures are retried. A repository created by a failed
+/// attempt is removed again.
+pub fn update_bare_mirror(
+    path: &Path,
+    urls: &[RepoUrl],
+    network: &NetworkOptions,
+) -> Result<usize> {
+    let existed = path.exists();
+    let what = format!("Fetching {}", urls.first().map_or("", |url| url.as_str()));
+
+    network.retry(&what, |_| {
+        let result = fetch_bare_mirror(path, urls, network);
+        if result.is_err() && !existed && path.exists() {
+            fs::remove_dir_all(path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        result
+    })
+}
+
+/// One attempt of [`update_bare_mirror`]
+fn fetch_bare_mirror(path: &Path, urls: &[RepoUrl], network: &NetworkOptions) -> Result<usize> {
+    let repo = if path.exists() {
+        Repository::open_bare(path)
+            .with_context(|| format!("Failed to open bare repository {}", path.display()))?
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let repo = Repository::init_bare(path)?;
+        if let Some(url) = urls.first() {
+            repo.remote("origin", url.as_str())?;
+        }
+        repo
+    };
+
+    Ok(fetch_with_fallbacks(
+        &repo,
+        "origin",
+        urls,
+        MIRROR_REFSPECS,
+        network,
+    )?)
+}
+
+/// Create the refs of another copy of the repository as if they had
+/// been fetched from origin: branches become `origin/*` remote-tracking
+/// branches, tags are kept and other refs are ignored.
+pub fn set_origin_refs<'a, I>(repo: &Repository, refs: I, log_message: &str) -> Result<()>
+where
+    I: IntoIterator<Item = (&'a str, Oid)>,
+{
+    for (name, target) in refs {
+        let local_name = if let Some(branch) = name.strip_prefix("refs/heads/") {
+            format!("refs/remotes/origin/{branch}")
+        } else if name.starts_with("refs/tags/") {
+            name.to_string()
+        } else {
+            continue;
+        };
+        repo.reference(&local_name, target, true, log_message)?;
+    }
     Ok(())
 }
 
@@ -40,6 +211,86 @@ pub fn checkout_to_spec(repo: &Repository, spec: &str, checkout: bool) -> Result
     Ok(())
 }
 
+/// The references to commits fetched by their hash.
+pub const FETCHED_REFS: &str = "refs/vcs2git/fetched/";
+
+/// Check if a version can be resolved without fetching
+pub fn has_version(repo: &Repository, version: &str) -> bool {
+    repo.revparse_single(version).is_ok()
+        || repo.revparse_single(&format!("origin/{version}")).is_ok()
+}
+
+/// Keep a reference to a version given as a full commit hash
+///
+/// Such a version is fetched without a branch, so nothing else records
+/// that the commit came from the remote.
+pub fn mark_fetched_commit(repo: &Repository, version: &str) -> Result<(), git2::Error> {
+    let Ok(id) = Oid::from_str(version) else {
+        return Ok(());
+    };
+    if version.len() == 40 && repo.find_commit(id).is_ok() {
+        repo.reference(
+            &format!("{FETCHED_REFS}{id}"),
+            id,
+            true,
+            "vcs2git: fetched by commit hash",
+        )?;
+    }
+    Ok(())
+}
+
+/// Resolve a version to a commit the way `checkout_to_version` does,
+/// without checking it out
+pub fn resolve_version(repo: &Repository, version: &str) -> Option<Oid> {
+    [version.to_string(), format!("origin/{version}")]
+        .iter()
+        .find_map(|spec| repo.revparse_single(spec).ok()?.peel_to_commit().ok())
+        .map(|commit| commit.id())
+}
+
+/// How an update moves a submodule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    Unchanged,
+    /// The new commit is a descendant of the current one.
+    FastForward,
+    /// The new commit is an ancestor of the current one.
+    Downgrade,
+    /// Neither commit is an ancestor of the other.
+    Divergent,
+}
+
+impl UpdateKind {
+    /// Whether the current commit stays in the history of the submodule.
+    pub fn is_fast_forward(self) -> bool {
+        matches!(self, Self::Unchanged | Self::FastForward)
+    }
+}
+
+impl fmt::Display for UpdateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Unchanged => "unchanged",
+            Self::FastForward => "fast-forward",
+            Self::Downgrade => "downgrade",
+            Self::Divergent => "divergent",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Classify the move of a submodule from one commit to another
+pub fn classify_update(repo: &Repository, from: Oid, to: Oid) -> UpdateKind {
+    if from == to {
+        return UpdateKind::Unchanged;
+    }
+    match repo.merge_base(from, to) {
+        Ok(base) if base == from => UpdateKind::FastForward,
+        Ok(base) if base == to => UpdateKind::Downgrade,
+        _ => UpdateKind::Divergent,
+    }
+}
+
 /// Checkout to a specific version (with fallback to origin/version)
 pub fn checkout_to_version(
     repo: &Repository,
@@ -63,8 +314,50 @@ pub fn checkout_to_version(
     Ok(())
 }
 
+/// Discard the local changes of a repository. Untracked files are
+/// kept unless they are in the way of tracked ones.
+pub fn discard_changes(repo: &Repository) -> Result<(), git2::Error> {
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))
+}
+
+/// Stash the local changes of a repository, and return whether there
+/// were any.
+pub fn stash_changes(repo: &mut Repository, include_untracked: bool) -> Result<bool, git2::Error> {
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("vcs2git", "vcs2git@localhost"))?;
+    let flags = match include_untracked {
+        true => StashFlags::INCLUDE_UNTRACKED,
+        false => StashFlags::DEFAULT,
+    };
+    match repo.stash_save(&signature, "vcs2git autostash", Some(flags)) {
+        Ok(_) => Ok(true),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Apply the latest stash again and drop it
+///
+/// Like `git stash pop`, conflicts are left in the working directory
+/// and the index, and the stash is kept.
+pub fn unstash_changes(repo: &mut Repository) -> Result<(), git2::Error> {
+    repo.stash_apply(0, None)?;
+    if repo.index()?.has_conflicts() {
+        return Err(git2::Error::new(
+            ErrorCode::MergeConflict,
+            ErrorClass::Stash,
+            "the local changes conflict with the update",
+        ));
+    }
+    repo.stash_drop(0)
+}
+
 /// Remove a submodule (for sync-selection)
-pub fn remove_submodule(repo: &Repository, path: &Path) -> Result<()> {
+///
+/// With `backup`, the module and working directories are moved there
+/// instead of being deleted, so that the removal can be rolled back.
+pub fn remove_submodule(repo: &Repository, path: &Path, backup: Option<&Path>) -> Result<()> {
     let path_str = path.to_string_lossy();
 
     // Find the submodule
@@ -76,6 +369,26 @@ pub fn remove_submodule(repo: &Repository, path: &Path) -> Result<()> {
         .name()
         .ok_or_else(|| color_eyre::eyre::eyre!("Submodule has no name"))?;
 
+    remove_submodule_steps(repo, name, path, backup, true)
+}
+
+/// Complete a removal by [`remove_submodule`] that was interrupted,
+/// skipping the steps that were already done
+pub fn finish_removal(repo: &Repository, name: &str, path: &Path, backup: &Path) -> Result<()> {
+    remove_submodule_steps(repo, name, path, Some(backup), false)
+}
+
+/// The steps of [`remove_submodule`]. Unless `must_exist` is set, a
+/// submodule missing from `.gitmodules` is not an error.
+fn remove_submodule_steps(
+    repo: &Repository,
+    name: &str,
+    path: &Path,
+    backup: Option<&Path>,
+    must_exist: bool,
+) -> Result<()> {
+    let path_str = path.to_string_lossy();
+
     // Step 1: Remove submodule configuration from .git/config (deinit)
     {
         let mut config = repo.config()?;
@@ -98,24 +411,66 @@ pub fn remove_submodule(repo: &Repository, path: &Path) -> Result<()> {
     }
 
     // Step 3: Remove from .gitmodules
-    update_gitmodules_file(repo, path, name, true)?;
+    update_gitmodules_file(repo, path, name, must_exist)?;
 
     // Step 4: Clean up .git/modules directory
     let modules_path = repo.path().join("modules").join(name);
     if modules_path.exists() {
-        fs::remove_dir_all(&modules_path)
+        discard_dir(&modules_path, backup.map(|dir| dir.join("modules")))
             .with_context(|| format!("Failed to remove modules directory for {name}"))?;
     }
 
     // Step 5: Remove working directory
     if path.exists() {
-        fs::remove_dir_all(path)
+        discard_dir(path, backup.map(|dir| dir.join("worktree")))
             .with_context(|| format!("Failed to remove working directory {path_str}"))?;
     }
 
     Ok(())
 }
 
+/// Check out the working directory of a submodule again from its
+/// module directory in `.git/modules`
+pub fn recreate_worktree(repo: &Repository, name: &str, path: &Path) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| color_eyre::eyre::eyre!("The repository has no working directory"))?;
+    let worktree = workdir.join(path);
+    fs::create_dir_all(&worktree)
+        .with_context(|| format!("Failed to create {}", worktree.display()))?;
+
+    // The same relative link that `git submodule add` writes, or an
+    // absolute one for nested submodules, whose Git directory is not
+    // inside the working directory
+    let gitdir = repo.path().join("modules").join(name);
+    let link = match gitdir.strip_prefix(workdir) {
+        Ok(relative) => {
+            let up = "../".repeat(path.components().count());
+            format!("{up}{}", relative.display())
+        }
+        Err(_) => gitdir.display().to_string(),
+    };
+    fs::write(worktree.join(".git"), format!("gitdir: {link}\n"))?;
+
+    let sub_repo = Repository::open(&worktree)?;
+    sub_repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    Ok(())
+}
+
+/// Delete a directory, or move it to `backup` if given
+fn discard_dir(dir: &Path, backup: Option<PathBuf>) -> Result<()> {
+    match backup {
+        Some(backup) => {
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(dir, &backup)?;
+        }
+        None => fs::remove_dir_all(dir)?,
+    }
+    Ok(())
+}
+
 /// Remove a submodule during rollback (more lenient, for partially created submodules)
 pub fn remove_submodule_rollback(repo: &Repository, path: &Path) -> Result<()> {
     let path_str = path.to_string_lossy();
@@ -332,4 +687,52 @@ mod tests {
         // Should be able to checkout to HEAD
         assert!(checkout_to_spec(&repo, "HEAD", false).is_ok());
     }
+
+    #[test]
+    fn test_stash_changes() {
+        let (dir, mut repo) = create_test_repo().unwrap();
+
+        // Nothing to stash
+        assert!(!stash_changes(&mut repo, true).unwrap());
+
+        // Untracked files only with include_untracked
+        fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+        assert!(!stash_changes(&mut repo, false).unwrap());
+        assert!(stash_changes(&mut repo, true).unwrap());
+        assert!(!dir.path().join("notes.txt").exists());
+
+        unstash_changes(&mut repo).unwrap();
+        assert!(dir.path().join("notes.txt").exists());
+        assert!(repo.stash_drop(0).is_err());
+    }
+
+    #[test]
+    fn test_classify_update() {
+        let (_dir, repo) = create_test_repo().unwrap();
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let base = repo.head().unwrap().peel_to_commit().unwrap();
+        let tree = base.tree().unwrap();
+        let ahead = repo
+            .commit(None, &sig, &sig, "Ahead", &tree, &[&base])
+            .unwrap();
+        let other = repo
+            .commit(None, &sig, &sig, "Other", &tree, &[&base])
+            .unwrap();
+
+        assert_eq!(
+            classify_update(&repo, base.id(), base.id()),
+            UpdateKind::Unchanged
+        );
+        assert_eq!(
+            classify_update(&repo, base.id(), ahead),
+            UpdateKind::FastForward
+        );
+        assert_eq!(
+            classify_update(&repo, ahead, base.id()),
+            UpdateKind::Downgrade
+        );
+        assert_eq!(classify_update(&repo, ahead, other), UpdateKind::Divergent);
+        assert_eq!(resolve_version(&repo, &ahead.to_string()), Some(ahead));
+        assert_eq!(resolve_version(&repo, "no-such-branch"), None);
+    }
 }
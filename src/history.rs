@@ -0,0 +1,151 @@
+use crate::{state::SubmoduleStateTracker, trash::Trash};
+use color_eyre::{eyre::Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The history directory, relative to the Git directory of the
+/// superproject.
+const HISTORY_DIR: &str = "vcs2git/history";
+
+/// The snapshot file in the history directory.
+const SNAPSHOT_FILE: &str = "snapshot.yaml";
+
+/// The superproject before the last completed run, kept so that the
+/// run can be undone
+///
+/// Only the last run is kept. The submodules it removed are in the
+/// trash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    /// The command line arguments of the run, without the program name.
+    args: Vec<String>,
+    /// The commit checked out when the run completed.
+    head: Option<String>,
+    /// The submodules added by the run.
+    added: Vec<PathBuf>,
+    /// The trash entries of the submodules removed by the run.
+    #[serde(default)]
+    trashed: Vec<String>,
+    /// The submodules before the run.
+    original: SubmoduleStateTracker,
+}
+
+impl History {
+    /// Record a completed run, replacing the previous one.
+    pub fn save(
+        repo: &Repository,
+        args: Vec<String>,
+        added: &[&Path],
+        trashed: Vec<String>,
+        tracker: &SubmoduleStateTracker,
+    ) -> Result<()> {
+        let history = Self {
+            args,
+            head: head_commit(repo).map(|id| id.to_string()),
+            added: added.iter().map(|path| path.to_path_buf()).collect(),
+            trashed,
+            original: tracker.clone(),
+        };
+
+        let dir = repo.path().join(HISTORY_DIR);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let path = dir.join(SNAPSHOT_FILE);
+        let text = serde_yaml::to_string(&history)?;
+        fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Read the record of the last run, if there is one.
+    pub fn load(repo: &Repository) -> Result<Option<Self>> {
+        let path = repo.path().join(HISTORY_DIR).join(SNAPSHOT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let history = serde_yaml::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(history))
+    }
+
+    /// The command line arguments of the run.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Whether commits were made or checked out since the run.
+    pub fn head_moved(&self, repo: &Repository) -> bool {
+        head_commit(repo).map(|id| id.to_string()) != self.head
+    }
+
+    /// The submodules added by the run.
+    pub fn added_paths(&self) -> Vec<&Path> {
+        self.added.iter().map(PathBuf::as_path).collect()
+    }
+
+    /// A tracker of the submodule states before the run.
+    pub fn tracker(&self) -> SubmoduleStateTracker {
+        self.original.clone()
+    }
+
+    /// Take the submodules removed by the run out of the trash, to
+    /// where the tracker restores them from.
+    pub fn restore_backups(&self, repo: &Repository, trash: &Trash) -> Result<()> {
+        // Check all entries before moving any
+        for id in &self.trashed {
+            trash
+                .get(id)
+                .with_context(|| "A submodule removed by the run was purged")?;
+        }
+        for id in &self.trashed {
+            trash.take(repo, id)?;
+        }
+        Ok(())
+    }
+
+    /// Delete the record of the last run.
+    pub fn remove(repo: &Repository) -> Result<()> {
+        let dir = repo.path().join(HISTORY_DIR);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// The commit HEAD points to, if any.
+fn head_commit(repo: &Repository) -> Option<Oid> {
+    repo.head().ok()?.target()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_history_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        assert!(History::load(&repo).unwrap().is_none());
+
+        let args = vec!["deps.repos".to_string(), "src".to_string()];
+        let tracker = SubmoduleStateTracker::default();
+        History::save(&repo, args.clone(), &[Path::new("src/b")], vec![], &tracker).unwrap();
+
+        let history = History::load(&repo).unwrap().unwrap();
+        assert_eq!(history.args(), args);
+        assert_eq!(history.added_paths(), vec![Path::new("src/b")]);
+        assert!(!history.head_moved(&repo));
+
+        History::remove(&repo).unwrap();
+        assert!(History::load(&repo).unwrap().is_none());
+    }
+}
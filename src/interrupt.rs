@@ -0,0 +1,65 @@
+use color_eyre::{eyre::Context, Result};
+use std::{
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{error, warn};
+
+/// The exit status after a second interrupt, as for a shell killed by
+/// SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// How often a wait checks whether the run was interrupted.
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+/// Set once SIGINT, SIGTERM or SIGHUP was received.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle SIGINT, SIGTERM and SIGHUP by cancelling the run, so that its
+/// changes are rolled back. A second signal exits immediately.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            error!(
+                "Interrupted again. Exiting without rolling back; run 'vcs2git abort' to clean up."
+            );
+            process::exit(EXIT_INTERRUPTED);
+        }
+        warn!("Interrupted. Cancelling and rolling back; interrupt again to exit immediately.");
+    })
+    .wrap_err("Failed to install the signal handler")
+}
+
+/// Check if the run was interrupted.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// The error for an operation cancelled by an interrupt.
+pub fn interrupted_error() -> git2::Error {
+    git2::Error::from_str("Interrupted")
+}
+
+/// Fail if the run was interrupted.
+pub fn check() -> Result<(), git2::Error> {
+    if is_interrupted() {
+        Err(interrupted_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Wait for the given time, but fail as soon as the run is interrupted.
+pub fn sleep(duration: Duration) -> Result<(), git2::Error> {
+    let end = Instant::now() + duration;
+    loop {
+        check()?;
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(left.min(SLEEP_SLICE));
+    }
+}
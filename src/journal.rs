@@ -0,0 +1,252 @@
+use crate::state::SubmoduleStateTracker;
+use color_eyre::{eyre::Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The journal file, relative to the Git directory of the superproject.
+const JOURNAL_FILE: &str = "vcs2git/journal.yaml";
+
+/// The on-disk record of an import run, written before each step so
+/// that a run that was killed can be resumed or rolled back
+///
+/// The journal exists from the first change to the superproject until
+/// the run completed or was rolled back.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    content: RefCell<JournalContent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalContent {
+    /// The command line arguments of the run, without the program name.
+    args: Vec<String>,
+    /// The submodules before the run.
+    original: SubmoduleStateTracker,
+    /// The steps started so far, in order.
+    steps: Vec<Step>,
+}
+
+/// A step of an import run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub operation: Operation,
+    pub path: PathBuf,
+    /// Whether the step completed or was rolled back.
+    pub done: bool,
+    /// Whether the step failed and was rolled back in keep-going mode.
+    #[serde(default)]
+    pub failed: bool,
+    /// Whether the local changes of the submodule are in its latest
+    /// stash while it is updated.
+    #[serde(default)]
+    pub stashed: bool,
+}
+
+/// What a step does to a submodule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Add,
+    Update,
+    Remove,
+}
+
+impl Journal {
+    /// Start the journal of a run.
+    pub fn create(
+        repo: &Repository,
+        args: Vec<String>,
+        tracker: &SubmoduleStateTracker,
+    ) -> Result<Self> {
+        let journal = Self {
+            path: repo.path().join(JOURNAL_FILE),
+            content: RefCell::new(JournalContent {
+                args,
+                original: tracker.clone(),
+                steps: vec![],
+            }),
+        };
+        journal.write()?;
+        Ok(journal)
+    }
+
+    /// Read the journal of an interrupted run, if there is one.
+    pub fn load(repo: &Repository) -> Result<Option<Self>> {
+        let path = repo.path().join(JOURNAL_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let content = serde_yaml::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(Self {
+            path,
+            content: RefCell::new(content),
+        }))
+    }
+
+    /// The command line arguments of the run.
+    pub fn args(&self) -> Vec<String> {
+        self.content.borrow().args.clone()
+    }
+
+    /// A tracker of the submodule states before the run.
+    pub fn tracker(&self) -> SubmoduleStateTracker {
+        self.content.borrow().original.clone()
+    }
+
+    /// The step that was in progress when the run was interrupted.
+    pub fn interrupted_step(&self) -> Option<Step> {
+        self.content
+            .borrow()
+            .steps
+            .last()
+            .filter(|step| !step.done)
+            .cloned()
+    }
+
+    /// The submodules added, or being added, by the run. Failed
+    /// additions that were rolled back are not included.
+    pub fn added_paths(&self) -> Vec<PathBuf> {
+        self.content
+            .borrow()
+            .steps
+            .iter()
+            .filter(|step| step.operation == Operation::Add && !step.failed)
+            .map(|step| step.path.clone())
+            .collect()
+    }
+
+    /// The submodules removed by the run so far.
+    pub fn removed_paths(&self) -> Vec<PathBuf> {
+        self.content
+            .borrow()
+            .steps
+            .iter()
+            .filter(|step| step.operation == Operation::Remove && step.done && !step.failed)
+            .map(|step| step.path.clone())
+            .collect()
+    }
+
+    /// Record that a step is about to start.
+    pub fn begin(&self, operation: Operation, path: &Path) -> Result<()> {
+        self.content.borrow_mut().steps.push(Step {
+            operation,
+            path: path.to_path_buf(),
+            done: false,
+            failed: false,
+            stashed: false,
+        });
+        self.write()
+    }
+
+    /// Record whether the local changes of the submodule of the current
+    /// step are stashed.
+    pub fn set_stashed(&self, stashed: bool) -> Result<()> {
+        if let Some(step) = self.content.borrow_mut().steps.last_mut() {
+            step.stashed = stashed;
+        }
+        self.write()
+    }
+
+    /// Record that the last step completed or was rolled back.
+    pub fn finish(&self) -> Result<()> {
+        if let Some(step) = self.content.borrow_mut().steps.last_mut() {
+            step.done = true;
+        }
+        self.write()
+    }
+
+    /// Record that the current step failed and was rolled back, in
+    /// keep-going mode.
+    pub fn fail(&self) -> Result<()> {
+        if let Some(step) = self.content.borrow_mut().steps.last_mut() {
+            step.done = true;
+            step.failed = true;
+        }
+        self.write()
+    }
+
+    /// Delete the journal once the run completed or was rolled back.
+    pub fn remove(self) -> Result<()> {
+        fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove {}", self.path.display()))
+    }
+
+    /// Replace the journal file, so that it is complete even if the
+    /// process is killed while writing it.
+    fn write(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let temp_path = self.path.with_extension("yaml.tmp");
+        let text = serde_yaml::to_string(&*self.content.borrow())?;
+        fs::write(&temp_path, text)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        assert!(Journal::load(&repo).unwrap().is_none());
+
+        let args = vec!["deps.repos".to_string(), "src".to_string()];
+        let tracker = SubmoduleStateTracker::default();
+        let journal = Journal::create(&repo, args.clone(), &tracker).unwrap();
+        journal.begin(Operation::Add, Path::new("src/a")).unwrap();
+        journal.finish().unwrap();
+        journal
+            .begin(Operation::Update, Path::new("src/b"))
+            .unwrap();
+        journal.begin(Operation::Add, Path::new("src/d")).unwrap();
+        journal.fail().unwrap();
+        journal.begin(Operation::Add, Path::new("src/c")).unwrap();
+        journal.set_stashed(true).unwrap();
+
+        let loaded = Journal::load(&repo).unwrap().unwrap();
+        assert_eq!(loaded.args(), args);
+        assert_eq!(
+            loaded.interrupted_step(),
+            Some(Step {
+                operation: Operation::Add,
+                path: PathBuf::from("src/c"),
+                done: false,
+                failed: false,
+                stashed: true,
+            })
+        );
+        assert_eq!(
+            loaded.added_paths(),
+            [PathBuf::from("src/a"), PathBuf::from("src/c")]
+        );
+
+        loaded.finish().unwrap();
+        assert_eq!(
+            Journal::load(&repo).unwrap().unwrap().interrupted_step(),
+            None
+        );
+
+        loaded.remove().unwrap();
+        assert!(Journal::load(&repo).unwrap().is_none());
+    }
+}
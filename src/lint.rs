@@ -0,0 +1,364 @@
+use crate::vcs::{Repo, RepoType, VersionKind};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Keys understood in a repository entry.
+const KNOWN_KEYS: &[&str] = &["type", "url", "version", "fallback_urls"];
+
+/// A check performed by `vcs2git lint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Rule {
+    /// The version names a branch, so the checkout may change over time.
+    FloatingVersion,
+    /// The version is a shortened commit hash.
+    AbbreviatedSha,
+    /// The URL uses an unencrypted transport (`http://` or `git://`).
+    InsecureUrl,
+    /// Some URLs end with `.git` and others do not.
+    GitSuffix,
+    /// The same repository is listed at more than one path.
+    DuplicateUrl,
+    /// The path lies inside the path of another entry.
+    NestedPath,
+    /// The entry contains a key vcs2git does not understand.
+    UnknownKey,
+    /// The repository type is not `git`.
+    UnsupportedType,
+    /// The entry cannot be parsed at all.
+    InvalidEntry,
+}
+
+impl Rule {
+    /// The severity used when not overridden on the command line.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::FloatingVersion
+            | Rule::AbbreviatedSha
+            | Rule::InsecureUrl
+            | Rule::GitSuffix
+            | Rule::UnknownKey => Severity::Warning,
+            Rule::DuplicateUrl | Rule::NestedPath | Rule::UnsupportedType | Rule::InvalidEntry => {
+                Severity::Error
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("every rule has a name");
+        f.write_str(value.get_name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Allow,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Severity::Allow => "allow",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.write_str(text)
+    }
+}
+
+/// A single problem found in a repos file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// The entry the problem belongs to, or an empty path for the
+    /// file as a whole.
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.as_os_str().is_empty() {
+            write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+        } else {
+            write!(
+                f,
+                "{}[{}] {}: {}",
+                self.severity,
+                self.rule,
+                self.path.display(),
+                self.message
+            )
+        }
+    }
+}
+
+/// Collects diagnostics, applying the configured severities
+struct Linter {
+    severities: HashMap<Rule, Severity>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn report(&mut self, rule: Rule, path: &Path, message: String) {
+        let severity = self
+            .severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity());
+
+        if severity == Severity::Allow {
+            return;
+        }
+
+        self.diagnostics.push(Diagnostic {
+            rule,
+            severity,
+            path: path.to_path_buf(),
+            message,
+        });
+    }
+}
+
+/// Check the content of a repos file and report every problem found.
+///
+/// Later overrides take precedence over earlier ones for the same
+/// rule.
+pub fn lint_repos(content: &str, overrides: &[(Rule, Severity)]) -> Result<Vec<Diagnostic>> {
+    let mut linter = Linter {
+        severities: overrides.iter().copied().collect(),
+        diagnostics: Vec::new(),
+    };
+
+    let document: Mapping = serde_yaml::from_str(content)?;
+
+    for key in document.keys() {
+        if key.as_str() != Some("repositories") {
+            linter.report(
+                Rule::UnknownKey,
+                Path::new(""),
+                format!("unknown top-level key {}", describe_key(key)),
+            );
+        }
+    }
+
+    let entries = match document.get("repositories") {
+        Some(Value::Mapping(entries)) => entries,
+        Some(_) => bail!("'repositories' must be a mapping"),
+        None => bail!("The repos file has no 'repositories' key"),
+    };
+
+    let mut parsed: Vec<(PathBuf, Repo)> = Vec::new();
+
+    for (key, entry) in entries {
+        let path = PathBuf::from(
+            key.as_str()
+                .ok_or_else(|| eyre!("Repository path {} is not a string", describe_key(key)))?,
+        );
+
+        if let Value::Mapping(fields) = entry {
+            for field in fields.keys() {
+                let is_known = field
+                    .as_str()
+                    .is_some_and(|name| KNOWN_KEYS.contains(&name));
+                if !is_known {
+                    linter.report(
+                        Rule::UnknownKey,
+                        &path,
+                        format!("unknown key {}", describe_key(field)),
+                    );
+                }
+            }
+        }
+
+        match serde_yaml::from_value::<Repo>(entry.clone()) {
+            Ok(repo) => parsed.push((path, repo)),
+            Err(err) => linter.report(Rule::InvalidEntry, &path, err.to_string()),
+        }
+    }
+
+    lint_entries(&mut linter, &parsed);
+
+    Ok(linter.diagnostics)
+}
+
+fn lint_entries(linter: &mut Linter, entries: &[(PathBuf, Repo)]) {
+    let with_suffix = entries
+        .iter()
+        .filter(|(_, repo)| repo.url.path().ends_with(".git"))
+        .count();
+    let without_suffix = entries.len() - with_suffix;
+    // Flag the minority spelling, or the suffix-less one on a tie.
+    let flag_suffixed = with_suffix < without_suffix;
+
+    let mut seen_urls: HashMap<String, &Path> = HashMap::new();
+
+    for (path, repo) in entries {
+        if let RepoType::Unknown(ty) = &repo.r#type {
+            linter.report(
+                Rule::UnsupportedType,
+                path,
+                format!("repository type '{ty}' is not supported"),
+            );
+        }
+
+        match VersionKind::of(&repo.version) {
+            VersionKind::Branch => linter.report(
+                Rule::FloatingVersion,
+                path,
+                format!(
+                    "version '{}' looks like a branch and may move over time",
+                    repo.version
+                ),
+            ),
+            VersionKind::AbbreviatedCommit => linter.report(
+                Rule::AbbreviatedSha,
+                path,
+                format!("version '{}' is an abbreviated commit hash", repo.version),
+            ),
+            VersionKind::Commit | VersionKind::Tag => {}
+        }
+
+        let scheme = repo.url.scheme();
+        if scheme == "http" || scheme == "git" {
+            linter.report(
+                Rule::InsecureUrl,
+                path,
+                format!("URL {} uses the unencrypted '{scheme}' scheme", repo.url),
+            );
+        }
+
+        if with_suffix > 0 && without_suffix > 0 {
+            let has_suffix = repo.url.path().ends_with(".git");
+            if has_suffix == flag_suffixed {
+                let message = if has_suffix {
+                    format!("URL {} ends with '.git' unlike most entries", repo.url)
+                } else {
+                    format!("URL {} has no '.git' suffix unlike most entries", repo.url)
+                };
+                linter.report(Rule::GitSuffix, path, message);
+            }
+        }
+
+        let normalized = repo.url.normalized();
+        match seen_urls.get(&normalized) {
+            Some(other) => linter.report(
+                Rule::DuplicateUrl,
+                path,
+                format!("URL {} is also used by {}", repo.url, other.display()),
+            ),
+            None => {
+                seen_urls.insert(normalized, path);
+            }
+        }
+
+        for (other, _) in entries {
+            if other != path && path.starts_with(other) {
+                linter.report(
+                    Rule::NestedPath,
+                    path,
+                    format!("path is nested inside {}", other.display()),
+                );
+            }
+        }
+    }
+}
+
+fn describe_key(key: &Value) -> String {
+    match key {
+        Value::String(name) => format!("'{name}'"),
+        other => format!("{other:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<(Rule, &Path)> {
+        diagnostics
+            .iter()
+            .map(|diag| (diag.rule, diag.path.as_path()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_reports_all_problems() {
+        let content = r#"repositories:
+  core/a:
+    type: git
+    url: https://github.com/org/a.git
+    version: main
+  core/a/nested:
+    type: git
+    url: http://github.com/org/b.git
+    version: 1228a68
+  core/c:
+    type: hg
+    url: https://github.com/org/a
+    version: 1.0.0
+    extra: true
+"#;
+        let diagnostics = lint_repos(content, &[]).unwrap();
+
+        assert_eq!(
+            rules(&diagnostics),
+            vec![
+                (Rule::UnknownKey, Path::new("core/c")),
+                (Rule::FloatingVersion, Path::new("core/a")),
+                (Rule::AbbreviatedSha, Path::new("core/a/nested")),
+                (Rule::InsecureUrl, Path::new("core/a/nested")),
+                (Rule::NestedPath, Path::new("core/a/nested")),
+                (Rule::UnsupportedType, Path::new("core/c")),
+                (Rule::GitSuffix, Path::new("core/c")),
+                (Rule::DuplicateUrl, Path::new("core/c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_severity_overrides() {
+        let content = r#"repositories:
+  core/a:
+    type: git
+    url: https://github.com/org/a.git
+    version: main
+"#;
+        let diagnostics = lint_repos(content, &[]).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+
+        let diagnostics = lint_repos(content, &[(Rule::FloatingVersion, Severity::Error)]).unwrap();
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        let diagnostics = lint_repos(content, &[(Rule::FloatingVersion, Severity::Allow)]).unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_lint_invalid_entry() {
+        let content = r#"repositories:
+  core/a:
+    type: git
+    version: 1.0.0
+"#;
+        let diagnostics = lint_repos(content, &[]).unwrap();
+        assert_eq!(
+            rules(&diagnostics),
+            vec![(Rule::InvalidEntry, Path::new("core/a"))]
+        );
+    }
+}
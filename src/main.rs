@@ -1,31 +1,70 @@
+mod bundle;
+mod cache;
 mod cli;
+mod config;
 mod git_ops;
+mod history;
+mod interrupt;
+mod journal;
+mod lint;
+mod mirror;
+mod network;
+mod policy;
 mod progress;
+mod remote;
+mod repo_url;
 mod state;
+mod summary;
+mod trash;
 mod utils;
 mod validation;
 mod vcs;
+mod verify;
 
 use crate::{
-    cli::Opts,
-    git_ops::{checkout_to_version, fetch, remove_submodule, remove_submodule_rollback},
+    bundle::{create_bundles, BundleDir},
+    cache::ObjectCache,
+    cli::{
+        BootstrapOpts, BundleCommand, BundleCreateOpts, Cli, Command, LintOpts, MirrorOpts,
+        NetworkArgs, Opts, TrashCommand, TrashPurgeOpts, TrashRestoreOpts, UndoOpts, VerifyOpts,
+    },
+    config::Config,
+    git_ops::{
+        checkout_to_version, classify_update, discard_changes, fetch_with_fallbacks,
+        finish_removal, has_version, mark_fetched_commit, recreate_worktree, remove_submodule,
+        remove_submodule_rollback, resolve_version, set_socket_timeout, stash_changes,
+        unstash_changes, update_bare_mirror, UpdateKind,
+    },
+    history::History,
+    journal::{Journal, Operation, Step},
+    lint::{lint_repos, Severity},
+    mirror::MirrorDir,
+    network::NetworkOptions,
+    policy::Policy,
     progress::ProgressReporter,
+    remote::UrlResolver,
+    repo_url::RepoUrl,
     state::SubmoduleStateTracker,
+    summary::RunSummary,
+    trash::{format_age, Trash},
     utils::{check_disjoint, check_subset},
-    validation::{validate_main_repo_clean, validate_repositories, validate_submodule_states},
+    validation::{
+        validate_bundles, validate_main_repo_clean, validate_mirror, validate_policy,
+        validate_repositories, validate_submodule_states, validate_unpushed_work,
+    },
     vcs::{Repo, RepoType, ReposFile},
+    verify::{verify_checkouts, verify_submodules},
 };
-use clap::Parser;
 use color_eyre::{
-    eyre::{bail, ensure, Context},
+    eyre::{bail, ensure, eyre, Context},
     Result,
 };
-use git2::Repository;
+use git2::{ConfigLevel, Oid, Repository};
 use std::{
     collections::{HashMap, HashSet},
-    fs::{self, File},
-    io::BufReader,
+    env, fs, iter,
     path::{Path, PathBuf},
+    thread,
 };
 use tracing::{error, info, warn};
 
@@ -36,13 +75,407 @@ fn main() -> Result<()> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
 
-    let opts = Opts::parse();
+    match Cli::parse() {
+        Cli::Import(opts) => run_import(opts, None),
+        Cli::Command(Command::Lint(opts)) => run_lint(&opts),
+        Cli::Command(Command::Verify(opts)) => run_verify(&opts),
+        Cli::Command(Command::Mirror(opts)) => run_mirror(&opts),
+        Cli::Command(Command::Bundle(BundleCommand::Create(opts))) => run_bundle_create(&opts),
+        Cli::Command(Command::Bootstrap(opts)) => run_bootstrap(&opts),
+        Cli::Command(Command::Resume) => run_resume(),
+        Cli::Command(Command::Abort) => run_abort(),
+        Cli::Command(Command::Undo(opts)) => run_undo(&opts),
+        Cli::Command(Command::Trash(TrashCommand::List)) => run_trash_list(),
+        Cli::Command(Command::Trash(TrashCommand::Restore(opts))) => run_trash_restore(&opts),
+        Cli::Command(Command::Trash(TrashCommand::Purge(opts))) => run_trash_purge(&opts),
+    }
+}
+
+fn run_resume() -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let journal =
+        Journal::load(&root_repo)?.ok_or_else(|| eyre!("There is no interrupted run to resume"))?;
+
+    let args = journal.args();
+    info!("Resuming the interrupted run: vcs2git {}", args.join(" "));
+    let Cli::Import(opts) = Cli::try_parse_from(iter::once("vcs2git".to_string()).chain(args))
+        .with_context(|| "Failed to parse the options of the interrupted run")?
+    else {
+        bail!("The interrupted run was not an import");
+    };
+    run_import(opts, Some(journal))
+}
+
+fn run_abort() -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let journal =
+        Journal::load(&root_repo)?.ok_or_else(|| eyre!("There is no interrupted run to abort"))?;
+
+    info!("Rolling back the interrupted run...");
+    let added = journal.added_paths();
+    let added: Vec<&Path> = added.iter().map(PathBuf::as_path).collect();
+    roll_back(&root_repo, &added, &journal.tracker())?;
+    journal.remove()?;
+
+    info!("The interrupted run was rolled back.");
+    Ok(())
+}
+
+fn run_bootstrap(opts: &BootstrapOpts) -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let config = Config::discover(opts.config.as_deref())?;
+    let resolver = match (&opts.mirror_dir, opts.offline) {
+        (Some(dir), true) => {
+            UrlResolver::from_config_file(&config)?.with_mirror(MirrorDir::new(dir.clone()))
+        }
+        _ => UrlResolver::from_config_file(&config)?,
+    };
+    let network = setup_network(&opts.network, &config, &root_repo.config()?)?;
+    let cache = open_object_cache(
+        opts.reference_dir.as_deref(),
+        opts.cache,
+        opts.dissociate,
+        &config,
+    )?;
+
+    // Nested submodules are only known once their parents are checked
+    // out, so each level is set up before the next
+    let mut repos = vec![root_repo];
+    while let Some(repo) = repos.pop() {
+        init_submodules(&repo, &resolver, cache.as_ref(), &network, true, false)?;
+        if !opts.no_recursive {
+            for submodule in repo.submodules()? {
+                repos.push(submodule.open()?);
+            }
+        }
+    }
+
+    let root_repo = Repository::open(".")?;
+    let problems = verify_checkouts(&root_repo, !opts.no_recursive)?;
+    for problem in &problems {
+        error!("{problem}");
+    }
+    ensure!(
+        problems.is_empty(),
+        "Verification found {} problem(s)",
+        problems.len()
+    );
+    info!("All submodules are checked out at their recorded commits.");
+    Ok(())
+}
+
+fn run_undo(opts: &UndoOpts) -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    ensure!(
+        Journal::load(&root_repo)?.is_none(),
+        "A previous run was interrupted. Run 'vcs2git abort' to roll it back first."
+    );
+    let history = History::load(&root_repo)?.ok_or_else(|| eyre!("There is no run to undo"))?;
+    ensure!(
+        opts.force || !history.head_moved(&root_repo),
+        "Commits were made since the last run. Use --force to undo it anyway."
+    );
+
+    info!("Undoing the last run: vcs2git {}", history.args().join(" "));
+    history.restore_backups(&root_repo, &Trash::new(&root_repo))?;
+    roll_back(&root_repo, &history.added_paths(), &history.tracker())?;
+    History::remove(&root_repo)?;
+
+    info!("The last run was undone.");
+    Ok(())
+}
+
+fn run_trash_list() -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let entries = Trash::new(&root_repo).list()?;
+    if entries.is_empty() {
+        info!("The trash is empty.");
+        return Ok(());
+    }
+
+    let width = entries
+        .iter()
+        .map(|entry| entry.id.len())
+        .chain(["ID".len()])
+        .max()
+        .unwrap_or_default();
+    info!("{:width$}  {:14}  PATH", "ID", "REMOVED");
+    for entry in &entries {
+        let worktree = if entry.worktree {
+            " (with working directory)"
+        } else {
+            ""
+        };
+        info!(
+            "{:width$}  {:14}  {}{worktree}",
+            entry.id,
+            format_age(entry.removed),
+            entry.path.display()
+        );
+    }
+    Ok(())
+}
+
+fn run_trash_restore(opts: &TrashRestoreOpts) -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let trash = Trash::new(&root_repo);
+    let entry = trash.get(&opts.id)?;
+    trash.restore(&root_repo, &opts.id)?;
+    info!(
+        "Restored {} at commit {}",
+        entry.path.display(),
+        entry.commit
+    );
+    Ok(())
+}
+
+fn run_trash_purge(opts: &TrashPurgeOpts) -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let trash = Trash::new(&root_repo);
+    let ids: Vec<String> = if opts.all {
+        trash.list()?.into_iter().map(|entry| entry.id).collect()
+    } else {
+        opts.ids.clone()
+    };
+    for id in &ids {
+        trash.purge(id)?;
+        info!("Purged {id}");
+    }
+    Ok(())
+}
+
+fn run_bundle_create(opts: &BundleCreateOpts) -> Result<()> {
+    let repos_list = ReposFile::load(&opts.repo_file)?;
+    let config = Config::discover(opts.config.as_deref())?;
+    let resolver = UrlResolver::from_config_file(&config)?;
+    let network = setup_network(&opts.network, &config, &git2::Config::open_default()?)?;
+
+    for (path, repo) in &repos_list.repositories {
+        if let RepoType::Unknown(ty) = &repo.r#type {
+            bail!(
+                "{}: repository type '{ty}' is not supported",
+                path.display()
+            );
+        }
+    }
+
+    let manifest = create_bundles(&repos_list, &resolver, &network, &opts.out_dir)?;
+    info!(
+        "{} repositories bundled in {}",
+        manifest.repositories.len(),
+        opts.out_dir.display()
+    );
+    Ok(())
+}
+
+fn run_mirror(opts: &MirrorOpts) -> Result<()> {
+    let repos_list = ReposFile::load(&opts.repo_file)?;
+    let config = Config::discover(opts.config.as_deref())?;
+    let resolver = UrlResolver::from_config_file(&config)?;
+    let network = setup_network(&opts.network, &config, &git2::Config::open_default()?)?;
+    let mirror = MirrorDir::new(opts.dir.clone());
+
+    let mut mirrored = HashSet::new();
+    let mut failures = 0;
+
+    for (path, repo) in &repos_list.repositories {
+        if let RepoType::Unknown(ty) = &repo.r#type {
+            warn!(
+                "Skipping {}: repository type '{ty}' is not supported",
+                path.display()
+            );
+            continue;
+        }
+
+        // Repositories listed at several paths are mirrored once
+        let mirror_path = mirror.repo_path(&repo.url);
+        if !mirrored.insert(mirror_path.clone()) {
+            continue;
+        }
+
+        info!("Mirroring {} to {}", repo.url, mirror_path.display());
+        let candidates = resolver.resolve(repo)?.candidates();
+        if let Err(err) = update_bare_mirror(&mirror_path, &candidates, &network) {
+            error!("Failed to mirror {}: {err}", repo.url);
+            failures += 1;
+        }
+    }
+
+    ensure!(failures == 0, "Failed to mirror {failures} repositories");
+    info!(
+        "{} repositories mirrored in {}",
+        mirrored.len(),
+        opts.dir.display()
+    );
+    Ok(())
+}
+
+fn run_verify(opts: &VerifyOpts) -> Result<()> {
+    let root_repo = Repository::open(".")
+        .with_context(|| "Please run in the toplevel directory of the git repo")?;
+    let repos_list = ReposFile::load(&opts.repo_file)?;
+
+    let entries: Vec<(PathBuf, &Repo)> = repos_list
+        .repositories
+        .iter()
+        .map(|(path, repo)| (opts.prefix.join(path), repo))
+        .collect();
+    let entries = || entries.iter().map(|(path, repo)| (path.as_path(), *repo));
+
+    let mut problems: Vec<String> = match Policy::discover(opts.policy.as_deref())? {
+        Some(policy) => policy
+            .check(entries())
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        None => vec![],
+    };
+    problems.extend(verify_submodules(&root_repo, entries())?);
+
+    for problem in &problems {
+        error!("{problem}");
+    }
+
+    ensure!(
+        problems.is_empty(),
+        "Verification found {} problem(s)",
+        problems.len()
+    );
+    info!(
+        "All {} repositories verified.",
+        repos_list.repositories.len()
+    );
+    Ok(())
+}
+
+fn run_lint(opts: &LintOpts) -> Result<()> {
+    let content = fs::read_to_string(&opts.repo_file)
+        .with_context(|| format!("Failed to read {}", opts.repo_file.display()))?;
+    let diagnostics = lint_repos(&content, &opts.severity_overrides())?;
+
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            Severity::Error => error!("{diagnostic}"),
+            Severity::Warning => warn!("{diagnostic}"),
+            Severity::Allow => {}
+        }
+    }
+
+    let error_count = diagnostics
+        .iter()
+        .filter(|diag| diag.severity == Severity::Error)
+        .count();
+    let warning_count = diagnostics.len() - error_count;
+    info!("{error_count} error(s), {warning_count} warning(s)");
+
+    ensure!(error_count == 0, "Lint found {error_count} error(s)");
+    Ok(())
+}
+
+/// Keep the submodules removed by a completed run in the trash and the
+/// original state for undo, and close the journal
+fn complete_run(
+    root_repo: &Repository,
+    journal: Journal,
+    trash: &Trash,
+    tracker: &SubmoduleStateTracker,
+    added: &[&Path],
+    opts: &Opts,
+) -> Result<()> {
+    let removed = journal.removed_paths();
+    let trashed = trash.keep_removed(root_repo, tracker, &removed, opts.trash_worktree)?;
+    SubmoduleStateTracker::discard_backups(root_repo)?;
+    trash.discard_reused(root_repo, added)?;
+    History::save(root_repo, journal.args(), added, trashed, tracker)?;
+    journal.remove()
+}
 
+/// Bring the submodule of the step an interrupted run was killed in
+/// to a consistent state before the run is resumed
+///
+/// A half-added submodule is added again from scratch, and a
+/// half-updated one is restored and updated again. A half-removed
+/// submodule is removed completely.
+fn reconcile_interrupted_step(
+    root_repo: &Repository,
+    journal: &Journal,
+    step: &Step,
+) -> Result<()> {
+    let path = step.path.as_path();
+    let tracker = journal.tracker();
+    let original = || {
+        tracker
+            .states()
+            .find(|state| state.path() == path)
+            .ok_or_else(|| eyre!("{} is not in the journal", path.display()))
+    };
+
+    match step.operation {
+        Operation::Add => {
+            info!("Removing the partially added {}", path.display());
+            remove_submodule_rollback(root_repo, path)?;
+        }
+        Operation::Update => {
+            info!("Restoring the partially updated {}", path.display());
+            let name = original()?.name();
+            let mut subrepo = root_repo.find_submodule(name)?.open()?;
+            // The local changes, if any, are in the stash
+            discard_changes(&subrepo)?;
+            tracker.restore(root_repo, name)?;
+            if step.stashed {
+                unstash_changes(&mut subrepo).with_context(|| {
+                    format!("Failed to restore the local changes of {}", path.display())
+                })?;
+            }
+            journal.finish()?;
+        }
+        Operation::Remove => {
+            info!("Finishing the removal of {}", path.display());
+            let name = original()?.name();
+            let backup = SubmoduleStateTracker::backup_dir(root_repo, name);
+            finish_removal(root_repo, name, path, &backup)?;
+            journal.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Import the repositories, or resume the interrupted run of the
+/// journal.
+fn run_import(opts: Opts, resumed: Option<Journal>) -> Result<()> {
     // Open the repository
     let mut root_repo = Repository::open(".")
         .with_context(|| "Please run in the toplevel directory of the git repo")?;
 
-    // List submodules
+    // Roll back instead of leaving a half-modified superproject when
+    // interrupted
+    interrupt::install_handler()?;
+
+    match &resumed {
+        Some(journal) => {
+            if let Some(step) = journal.interrupted_step() {
+                reconcile_interrupted_step(&root_repo, journal, &step)?;
+            }
+        }
+        None => {
+            ensure!(
+                Journal::load(&root_repo)?.is_none(),
+                "A previous run was interrupted. \
+                Run 'vcs2git resume' to continue it or 'vcs2git abort' to roll it back."
+            );
+        }
+    }
+
+    // List submodules. The ones removed before a resumed run was
+    // interrupted are still in HEAD.
+    let removed_earlier = resumed.as_ref().map(Journal::removed_paths);
     let submod_names: HashMap<PathBuf, String> = root_repo
         .submodules()?
         .into_iter()
@@ -51,13 +484,31 @@ fn main() -> Result<()> {
             let name = submod.name()?.to_string();
             Some((path, name))
         })
+        .filter(|(path, _)| {
+            !removed_earlier
+                .iter()
+                .flatten()
+                .any(|removed| removed == path)
+        })
         .collect();
 
     // Parse the repo list
-    let repos_list: ReposFile = {
-        let reader = BufReader::new(File::open(&opts.repo_file)?);
-        serde_yaml::from_reader(reader)?
-    };
+    let repos_list = ReposFile::load(&opts.repo_file)?;
+    let policy = Policy::discover(opts.policy.as_deref())?;
+    let config = Config::discover(opts.config.as_deref())?;
+    let resolver = UrlResolver::from_config(&opts, &config)?;
+    let network = setup_network(&opts.network, &config, &root_repo.config()?)?;
+    let cache = open_object_cache(
+        opts.reference_dir.as_deref(),
+        opts.cache,
+        opts.dissociate,
+        &config,
+    )?;
+    let bundles = opts
+        .from_bundles
+        .as_deref()
+        .map(BundleDir::load)
+        .transpose()?;
 
     ensure!(
         opts.prefix.is_relative(),
@@ -67,13 +518,23 @@ fn main() -> Result<()> {
     // Validate repository configuration
     validate_repositories(&repos_list.repositories, &opts.prefix)?;
 
-    // Check for uncommitted changes in the main repository
-    validate_main_repo_clean(&root_repo)?;
+    // Set up the submodules of a fresh clone
+    if opts.init && resumed.is_none() {
+        init_submodules(
+            &root_repo,
+            &resolver,
+            cache.as_ref(),
+            &network,
+            !opts.no_checkout,
+            opts.dry_run,
+        )?;
+    }
 
-    // Validate existing submodule states
-    info!("Checking existing submodule states...");
-    validate_submodule_states(&root_repo)?;
-    info!("All validation checks passed.");
+    // The changes of an interrupted run are expected
+    if resumed.is_none() {
+        // Check for uncommitted changes in the main repository
+        validate_main_repo_clean(&root_repo)?;
+    }
 
     let selected_repos: HashMap<PathBuf, _> = {
         let all_suffixes: HashSet<&Path> = repos_list
@@ -124,13 +585,83 @@ fn main() -> Result<()> {
         }
     }
 
+    let mut entries: Vec<(&Path, &Repo)> = selected_repos
+        .iter()
+        .map(|(path, repo)| (path.as_path(), *repo))
+        .collect();
+    entries.sort_unstable_by_key(|(path, _)| *path);
+
+    // Enforce the policy before anything is fetched
+    if let Some(policy) = &policy {
+        let resolved = entries
+            .iter()
+            .map(|&(path, repo)| {
+                let urls = resolver.resolve(repo)?;
+                // The mirror only serves copies of the entry URLs
+                let mut resolved = vec![urls.record.clone()];
+                if !resolver.is_offline() {
+                    resolved.extend(urls.candidates());
+                }
+                Ok((path, repo, resolved))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        validate_policy(policy, resolved)?;
+    }
+
+    // Make sure that everything can be fetched without the network
+    if let (true, Some(dir)) = (opts.offline, &opts.mirror_dir) {
+        validate_mirror(&MirrorDir::new(dir.clone()), entries.iter().copied())?;
+    }
+    if let Some(bundles) = &bundles {
+        validate_bundles(bundles, entries.iter().copied())?;
+    }
+
     let (new_repos, updated_submods, removed_repos) =
         classify_submodules(&selected_repos, &submod_names, &opts.prefix);
 
+    // Validate the states of the submodules that are updated or
+    // removed. The update takes care of local changes with
+    // --autostash or --force.
+    if resumed.is_none() {
+        info!("Checking existing submodule states...");
+        let updated: Vec<&Path> = match opts.should_update() {
+            true => updated_submods.iter().map(|(path, _)| *path).collect(),
+            false => vec![],
+        };
+        let mut affected = updated.clone();
+        if opts.sync_selection {
+            affected.extend(removed_repos.iter().map(|(path, _)| *path));
+        }
+        let handled: &[&Path] = match opts.autostash || opts.force {
+            true => &updated,
+            false => &[],
+        };
+        let scope = (!opts.strict).then_some(affected.as_slice());
+        validate_submodule_states(&root_repo, scope, opts.ignore_untracked, handled)?;
+        info!("All validation checks passed.");
+    }
+
+    // Make sure that no local work is lost
+    if !opts.force_remove {
+        let removed: Vec<&Path> = match opts.sync_selection {
+            true => removed_repos.iter().map(|(path, _)| *path).collect(),
+            false => vec![],
+        };
+        let updated: Vec<&Path> = match opts.should_update() {
+            true => updated_submods.iter().map(|(path, _)| *path).collect(),
+            false => vec![],
+        };
+        validate_unpushed_work(&root_repo, &removed, &updated)?;
+    }
+
     fs::create_dir_all(&opts.prefix)?;
 
-    // Capture original state before any modifications
-    let tracker = SubmoduleStateTracker::new(&root_repo)?;
+    // Capture original state before any modifications, or before the
+    // interrupted run
+    let tracker = match &resumed {
+        Some(journal) => journal.tracker(),
+        None => SubmoduleStateTracker::new(&root_repo)?,
+    };
 
     // Calculate total operations for progress reporting
     let total_operations = new_repos.len()
@@ -146,15 +677,47 @@ fn main() -> Result<()> {
         };
 
     if total_operations == 0 {
+        // The interrupted step was the last one
+        if let Some(journal) = resumed {
+            let added = journal.added_paths();
+            let added: Vec<&Path> = added.iter().map(PathBuf::as_path).collect();
+            let trash = Trash::new(&root_repo);
+            complete_run(&root_repo, journal, &trash, &tracker, &added, &opts)?;
+        }
         info!("No operations to perform - all repositories are up to date");
         return Ok(());
     }
 
+    // Record each step, so that the run can be resumed or rolled back
+    // if it is killed
+    let journal = match resumed {
+        Some(journal) => Some(journal),
+        None if opts.dry_run => None,
+        None => Some(Journal::create(
+            &root_repo,
+            env::args().skip(1).collect(),
+            &tracker,
+        )?),
+    };
+
     // Create progress reporter
     let progress = ProgressReporter::new(total_operations as u64);
+    let trash = Trash::new(&root_repo);
+    let ctx = ImportContext {
+        opts: &opts,
+        resolver: &resolver,
+        cache: cache.as_ref(),
+        bundles: bundles.as_ref(),
+        network: &network,
+        tracker: &tracker,
+        journal: journal.as_ref(),
+        trash: &trash,
+        progress: &progress,
+    };
 
     // Track which operations we've completed
     let mut completed_new = Vec::new();
+    let mut summary = RunSummary::default();
 
     // Process all operations with rollback on failure
     let result = process_submodule_operations(
@@ -162,38 +725,34 @@ fn main() -> Result<()> {
         &new_repos,
         &updated_submods,
         &removed_repos,
-        &opts,
+        &ctx,
         &mut completed_new,
-        &progress,
+        &mut summary,
     );
+    summary.report();
+
+    // Also the submodules added before a resumed run was interrupted
+    let earlier = journal.as_ref().map(Journal::added_paths);
+    let mut added: Vec<&Path> = earlier
+        .iter()
+        .flatten()
+        .map(PathBuf::as_path)
+        .chain(completed_new)
+        .collect();
+    added.sort_unstable();
+    added.dedup();
 
     // Handle rollback if operation failed
     if let Err(e) = result {
         if !opts.dry_run {
             error!("Operation failed. Rolling back all changes...");
 
-            // Remove any newly added submodules
-            for path in completed_new {
-                if let Err(remove_err) = remove_submodule_rollback(&root_repo, path) {
-                    warn!("Failed to remove {}: {}", path.display(), remove_err);
-                }
-            }
-
-            // Clean up .gitmodules if no submodules remain
-            let gitmodules_path = PathBuf::from(".gitmodules");
-            if gitmodules_path.exists() {
-                // Check if any submodules remain
-                let submodules = root_repo.submodules()?;
-                if submodules.is_empty() {
-                    // No submodules left, remove .gitmodules
-                    fs::remove_file(&gitmodules_path)?;
-                }
-            }
-
-            // Restore original states
-            if let Err(rollback_err) = tracker.rollback(&root_repo) {
+            if let Err(rollback_err) = roll_back(&root_repo, &added, &tracker) {
                 error!("Error during rollback: {rollback_err}");
             }
+            if let Some(journal) = journal {
+                journal.remove()?;
+            }
 
             bail!("Operation failed and was rolled back: {}", e);
         } else {
@@ -208,20 +767,414 @@ fn main() -> Result<()> {
         }
     }
 
+    // Keep the removed submodules in the trash and the original state
+    // for undo
+    if let Some(journal) = journal {
+        complete_run(&root_repo, journal, &trash, &tracker, &added, &opts)?;
+    }
+
+    // In keep-going mode, the failed repositories were rolled back one by one
+    let failures = summary.failure_count();
+    if failures > 0 {
+        remove_empty_gitmodules(&root_repo)?;
+        progress.finish_with_message("Completed with failures");
+        bail!("{failures} of {total_operations} operations failed");
+    }
+
     progress.finish_with_message("All operations completed successfully!");
 
     Ok(())
 }
 
+/// Undo the changes of a run: remove the added submodules and restore
+/// the original commits of the others
+fn roll_back(
+    root_repo: &Repository,
+    added: &[&Path],
+    tracker: &SubmoduleStateTracker,
+) -> Result<()> {
+    // Remove any newly added submodules
+    for path in added {
+        if let Err(remove_err) = remove_submodule_rollback(root_repo, path) {
+            warn!("Failed to remove {}: {}", path.display(), remove_err);
+        }
+    }
+
+    // Restore original states, including .gitmodules
+    tracker.rollback(root_repo)
+}
+
+/// Clean up .gitmodules if no submodules remain
+fn remove_empty_gitmodules(root_repo: &Repository) -> Result<()> {
+    let gitmodules_path = PathBuf::from(".gitmodules");
+    if gitmodules_path.exists() {
+        // Check if any submodules remain
+        let submodules = root_repo.submodules()?;
+        if submodules.is_empty() {
+            // No submodules left, remove .gitmodules
+            fs::remove_file(&gitmodules_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Settings and state shared by all operations of an import run
+struct ImportContext<'a> {
+    opts: &'a Opts,
+    resolver: &'a UrlResolver,
+    cache: Option<&'a ObjectCache>,
+    bundles: Option<&'a BundleDir>,
+    network: &'a NetworkOptions,
+    /// The submodule states before the run, to roll back to.
+    tracker: &'a SubmoduleStateTracker,
+    journal: Option<&'a Journal>,
+    /// Removed submodules, reused when added again.
+    trash: &'a Trash,
+    progress: &'a ProgressReporter,
+}
+
+/// Combine the network settings and apply the process-wide ones
+fn setup_network(
+    args: &NetworkArgs,
+    config: &Config,
+    git_config: &git2::Config,
+) -> Result<NetworkOptions> {
+    let network = NetworkOptions::new(args, &config.network, git_config)?;
+    network.install()?;
+    Ok(network)
+}
+
+/// Open the object cache selected by the options or the configuration
+fn open_object_cache(
+    reference_dir: Option<&Path>,
+    use_cache: bool,
+    dissociate: bool,
+    config: &Config,
+) -> Result<Option<ObjectCache>> {
+    let dir = match (reference_dir, use_cache, &config.cache_dir) {
+        (Some(dir), _, _) => dir.to_path_buf(),
+        (None, true, _) => ObjectCache::default_dir()
+            .ok_or_else(|| eyre!("Cannot determine the cache directory; use --reference-dir"))?,
+        (None, false, Some(dir)) => dir.clone(),
+        (None, false, None) => {
+            ensure!(
+                !dissociate,
+                "--dissociate requires --reference-dir or --cache"
+            );
+            return Ok(None);
+        }
+    };
+    Ok(Some(ObjectCache::new(dir, dissociate)))
+}
+
+/// Fetch the version into a submodule repository, borrowing objects from
+/// the object cache when one is used, or import it from a bundle, and
+/// keep a reference to a version given as a commit hash. Returns the
+/// index of the URL that was fetched from.
+fn fetch_submodule(
+    subrepo: &Repository,
+    info: &Repo,
+    record: &RepoUrl,
+    candidates: &[RepoUrl],
+    ctx: &ImportContext,
+) -> Result<usize> {
+    let version = info.version.as_str();
+    let used = fetch_version(
+        subrepo,
+        info,
+        record,
+        candidates,
+        ctx.bundles,
+        ctx.cache,
+        ctx.network,
+    )?;
+    mark_fetched_commit(subrepo, version)?;
+    Ok(used)
+}
+
+/// Fetch the version of a repository from the bundles, the cache or
+/// the remote, leaving `origin` at the recorded URL
+fn fetch_version(
+    subrepo: &Repository,
+    info: &Repo,
+    record: &RepoUrl,
+    candidates: &[RepoUrl],
+    bundles: Option<&BundleDir>,
+    cache: Option<&ObjectCache>,
+    network: &NetworkOptions,
+) -> Result<usize> {
+    let version = info.version.as_str();
+
+    let used = if let Some(bundles) = bundles {
+        bundles.import(subrepo, info)?;
+        0
+    } else {
+        match cache.map(|cache| cache.prepare(subrepo, candidates, network)) {
+            // The cache was just updated, so only fetch if it lacks the version
+            Some(Ok(used)) if has_version(subrepo, version) => used,
+            prepared => {
+                if let Some(Err(err)) = prepared {
+                    warn!("Cannot use the object cache for {}: {err}", candidates[0]);
+                }
+                fetch_with_fallbacks(subrepo, "origin", candidates, &[version], network)?
+            }
+        }
+    };
+
+    // The candidates may be a mirror or rewritten URLs
+    subrepo.remote_set_url("origin", record.as_str())?;
+    Ok(used)
+}
+
+/// A submodule recorded in the superproject whose repository is not
+/// set up
+struct UninitializedSubmodule {
+    name: String,
+    path: PathBuf,
+    url: RepoUrl,
+    /// The URLs to fetch from, in order.
+    candidates: Vec<RepoUrl>,
+    /// The commit recorded in the index.
+    commit: Oid,
+    /// Whether its repository in `.git/modules` was created here.
+    created: bool,
+}
+
+/// Initialize and check out the submodules that are not initialized,
+/// like `git submodule update --init`
+///
+/// The recorded commits are fetched in parallel, from the cache or the
+/// remote like the versions of added submodules. The cache is updated
+/// before, on this thread. Submodules that fail are left uninitialized.
+fn init_submodules(
+    root_repo: &Repository,
+    resolver: &UrlResolver,
+    cache: Option<&ObjectCache>,
+    network: &NetworkOptions,
+    checkout: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let workdir = root_repo
+        .workdir()
+        .ok_or_else(|| eyre!("The repository has no working directory"))?;
+
+    let mut pending = vec![];
+    for submodule in root_repo.submodules()? {
+        if submodule.workdir_id().is_some() {
+            continue;
+        }
+        let name = submodule
+            .name()
+            .ok_or_else(|| eyre!("Submodule without name"))?
+            .to_string();
+        let url = submodule
+            .url()
+            .ok_or_else(|| eyre!("Submodule {name} has no URL"))?;
+        let url: RepoUrl = url
+            .parse()
+            .with_context(|| format!("Invalid URL of submodule {name}"))?;
+        let commit = submodule
+            .index_id()
+            .ok_or_else(|| eyre!("Submodule {name} is not in the index"))?;
+        let candidates = resolver
+            .resolve(&recorded_version(&url, commit))?
+            .candidates();
+        pending.push(UninitializedSubmodule {
+            name,
+            path: submodule.path().to_path_buf(),
+            url,
+            candidates,
+            commit,
+            created: false,
+        });
+    }
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    if dry_run {
+        for submod in &pending {
+            info!("[DRY RUN] Would initialize {}", submod.path.display());
+        }
+        return Ok(());
+    }
+
+    // Set up the repositories, reusing the ones left by `git submodule
+    // deinit`
+    info!("Initializing {} submodules...", pending.len());
+    for submod in &mut pending {
+        let mut submodule = root_repo.find_submodule(&submod.name)?;
+        submodule.init(false)?;
+        if root_repo.path().join("modules").join(&submod.name).exists() {
+            recreate_worktree(root_repo, &submod.name, &submod.path)?;
+        } else {
+            submodule.repo_init(true)?;
+            submod.created = true;
+        }
+    }
+
+    // Update each cached copy once, so that the workers only borrow
+    // from it
+    if let Some(cache) = cache {
+        let mut updated = HashSet::new();
+        for submod in &pending {
+            if updated.insert(cache.repo_path(&submod.candidates[0])) {
+                if let Err(err) = cache.update(&submod.candidates, network) {
+                    warn!("Cannot use the object cache for {}: {err}", submod.url);
+                }
+            }
+        }
+    }
+
+    // The socket timeouts of libgit2 are process-wide, so the workers
+    // share the longest one any of them needs. Each fetch still stops
+    // at its own timeout while it is transferring.
+    let mut timeouts = vec![];
+    for url in pending.iter().flat_map(|submod| &submod.candidates) {
+        timeouts.push(network.fetch_timeout(url.as_str())?);
+    }
+    let longest = match timeouts.contains(&None) {
+        true => None,
+        false => timeouts.into_iter().flatten().max(),
+    };
+    set_socket_timeout(longest)?;
+    let shared_network = NetworkOptions {
+        shared_socket_timeout: true,
+        ..network.clone()
+    };
+
+    // Fetch and check out in parallel
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = pending.len().div_ceil(workers);
+    let results: Vec<Result<()>> = thread::scope(|scope| {
+        let handles: Vec<_> = pending
+            .chunks(chunk_size)
+            .map(|chunk| {
+                // The cache is only read, and the retry count is not
+                // shared
+                let cache = cache.cloned();
+                let network = shared_network.clone();
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|submod| {
+                            let what = format!("Initializing {}", submod.path.display());
+                            network.retry(&what, |_| {
+                                checkout_recorded_commit(
+                                    &workdir.join(&submod.path),
+                                    submod,
+                                    cache.as_ref(),
+                                    &network,
+                                    checkout,
+                                )
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Submodule initialization panicked"))
+            .collect()
+    });
+
+    let mut failures = 0;
+    for (submod, result) in pending.iter().zip(results) {
+        if let Err(e) = result {
+            error!("Failed to initialize {}: {}", submod.path.display(), e);
+            failures += 1;
+            if let Err(e) = deinit_submodule(root_repo, submod) {
+                warn!("Failed to clean up {}: {}", submod.path.display(), e);
+            }
+        }
+    }
+    ensure!(
+        failures == 0,
+        "{failures} submodules could not be initialized"
+    );
+    Ok(())
+}
+
+/// Fetch the commit recorded for an uninitialized submodule and check
+/// it out
+fn checkout_recorded_commit(
+    dir: &Path,
+    submod: &UninitializedSubmodule,
+    cache: Option<&ObjectCache>,
+    network: &NetworkOptions,
+    checkout: bool,
+) -> Result<()> {
+    let subrepo = Repository::open(dir)?;
+    if subrepo.find_remote("origin").is_err() {
+        subrepo.remote("origin", submod.url.as_str())?;
+    }
+
+    let info = recorded_version(&submod.url, submod.commit);
+    if !has_version(&subrepo, &info.version) {
+        fetch_version(
+            &subrepo,
+            &info,
+            &submod.url,
+            &submod.candidates,
+            None,
+            cache,
+            network,
+        )?;
+        mark_fetched_commit(&subrepo, &info.version)?;
+    }
+    checkout_to_version(&subrepo, &info.version, checkout)?;
+    Ok(())
+}
+
+/// A repository entry for the commit recorded for a submodule.
+fn recorded_version(url: &RepoUrl, commit: Oid) -> Repo {
+    Repo {
+        r#type: RepoType::Git,
+        url: url.clone(),
+        version: commit.to_string(),
+        fallback_urls: vec![],
+    }
+}
+
+/// Undo the initialization of a submodule.
+fn deinit_submodule(root_repo: &Repository, submod: &UninitializedSubmodule) -> Result<()> {
+    let workdir = root_repo
+        .workdir()
+        .ok_or_else(|| eyre!("The repository has no working directory"))?;
+    let gitlink = workdir.join(&submod.path).join(".git");
+    if gitlink.is_file() {
+        fs::remove_file(&gitlink)?;
+    }
+    if submod.created {
+        let modules = root_repo.path().join("modules").join(&submod.name);
+        if modules.exists() {
+            fs::remove_dir_all(&modules)?;
+        }
+    }
+    let _ = root_repo
+        .config()?
+        .open_level(ConfigLevel::Local)?
+        .remove(&format!("submodule.{}.url", submod.name));
+    Ok(())
+}
+
 fn process_submodule_operations<'a>(
     root_repo: &mut Repository,
     new_repos: &[(&'a Path, &'a &'a Repo)],
     updated_submods: &[(&'a Path, (&'a String, &'a &'a Repo))],
     removed_repos: &[(&'a Path, &'a String)],
-    opts: &Opts,
+    ctx: &ImportContext,
     completed_new: &mut Vec<&'a Path>,
-    progress: &ProgressReporter,
+    summary: &mut RunSummary,
 ) -> Result<()> {
+    let ImportContext {
+        opts,
+        resolver,
+        progress,
+        ..
+    } = ctx;
+
     // Add new repos
     for (path, info) in new_repos {
         if opts.dry_run {
@@ -230,40 +1183,80 @@ fn process_submodule_operations<'a>(
             continue;
         }
 
+        interrupt::check()?;
         progress.set_message(&format!("Adding {}", path.display()));
-        let Repo { url, version, .. } = info;
+        let Repo { version, .. } = info;
+        if let Some(journal) = ctx.journal {
+            journal.begin(Operation::Add, path)?;
+        }
 
-        // Track the path before attempting to create submodule
-        let result = (|| -> Result<()> {
-            let mut submod = root_repo.submodule(url.as_str(), path, true)?;
-            // At this point, .gitmodules has been modified
+        // A failed attempt leaves a half-created submodule behind, which
+        // is removed before trying again
+        let retries_before = ctx.network.retry_count();
+        let what = format!("Adding {}", path.display());
+        let result = ctx
+            .network
+            .retry(&what, |attempt| -> Result<Option<RepoUrl>> {
+                if attempt > 0 {
+                    remove_submodule_rollback(root_repo, path)?;
+                }
 
-            let subrepo = match submod.open() {
-                Ok(repo) => repo,
-                Err(e) => {
-                    // Submodule was created but clone failed - need cleanup
-                    error!("Failed to clone submodule: {e}");
-                    return Err(e.into());
+                let urls = resolver.resolve(info)?;
+                let mut submod = root_repo.submodule(urls.record.as_str(), path, true)?;
+                // At this point, .gitmodules has been modified
+
+                let subrepo = match submod.open() {
+                    Ok(repo) => repo,
+                    Err(e) => {
+                        // Submodule was created but clone failed - need cleanup
+                        error!("Failed to clone submodule: {e}");
+                        return Err(e.into());
+                    }
+                };
+
+                // Start from the same submodule removed earlier, so that
+                // its local branches come back and less is fetched
+                let name = submod.name().unwrap_or_default();
+                if let Err(err) = ctx.trash.reuse(name, urls.record.as_str(), &subrepo) {
+                    warn!("Cannot reuse {} from the trash: {err}", path.display());
                 }
-            };
 
-            // Get remote branches and tags
-            fetch(&subrepo, "origin", version)?;
+                // Get remote branches and tags
+                let candidates = urls.candidates();
+                let used = fetch_submodule(&subrepo, info, &urls.record, &candidates, ctx)?;
 
-            // Checkout
-            checkout_to_version(&subrepo, version, !opts.no_checkout)?;
+                // Checkout
+                checkout_to_version(&subrepo, version, !opts.no_checkout)?;
 
-            submod.add_finalize()?;
-            Ok(())
-        })();
+                submod.add_finalize()?;
+                Ok((used > 0).then(|| candidates[used].clone()))
+            });
+        summary.record_retries(path, ctx.network.retry_count() - retries_before);
+        if let Ok(Some(url)) = &result {
+            summary.record_fallback(path, url);
+        }
 
         match result {
             Ok(_) => {
                 completed_new.push(path);
+                if let Some(journal) = ctx.journal {
+                    journal.finish()?;
+                }
                 progress.inc(1);
             }
             Err(e) => {
                 error!("Failed to add {}: {}", path.display(), e);
+                if opts.keep_going && !interrupt::is_interrupted() {
+                    if let Err(remove_err) = remove_submodule_rollback(root_repo, path) {
+                        warn!("Failed to remove {}: {}", path.display(), remove_err);
+                    }
+                    summary.record_failure(path, "add", &e);
+                    if let Some(journal) = ctx.journal {
+                        journal.fail()?;
+                    }
+                    progress.inc(1);
+                    continue;
+                }
                 // The submodule entry was created but operation failed
                 completed_new.push(path);
                 return Err(e);
@@ -274,34 +1267,111 @@ fn process_submodule_operations<'a>(
     if opts.should_update() {
         for (path, (submod_name, info)) in updated_submods {
             if opts.dry_run {
-                progress.println(&format!("[DRY RUN] Would update {}", path.display()));
+                // Classified against what was fetched before
+                let subrepo = root_repo.find_submodule(submod_name)?.open()?;
+                let from = subrepo.head()?.target();
+                let message = match (from, resolve_version(&subrepo, &info.version)) {
+                    (Some(from), Some(to)) => classify_update(&subrepo, from, to).to_string(),
+                    _ => format!("{} is not fetched yet", info.version),
+                };
+                progress.println(&format!(
+                    "[DRY RUN] Would update {}: {message}",
+                    path.display()
+                ));
                 progress.inc(1);
                 continue;
             }
 
+            interrupt::check()?;
             progress.set_message(&format!("Updating {}", path.display()));
-            let Repo { url, version, .. } = info;
-            let result = (|| -> Result<()> {
-                root_repo.submodule_set_url(submod_name, url.as_str())?;
+            let Repo { version, .. } = info;
+            if let Some(journal) = ctx.journal {
+                journal.begin(Operation::Update, path)?;
+            }
+            let retries_before = ctx.network.retry_count();
+            let what = format!("Updating {}", path.display());
+            let result = ctx.network.retry(&what, |_| -> Result<_> {
+                let urls = resolver.resolve(info)?;
+                root_repo.submodule_set_url(submod_name, urls.record.as_str())?;
                 let mut submod = root_repo.find_submodule(submod_name)?;
-                let subrepo = submod.open()?;
+                let mut subrepo = submod.open()?;
+                let from = subrepo.head()?.target();
 
                 // Get remote branches and tags
-                fetch(&subrepo, "origin", version)?;
+                let candidates = urls.candidates();
+                let used = fetch_submodule(&subrepo, info, &urls.record, &candidates, ctx)?;
+
+                let kind = match (from, resolve_version(&subrepo, version)) {
+                    (Some(from), Some(to)) => classify_update(&subrepo, from, to),
+                    _ => UpdateKind::FastForward,
+                };
+                if opts.ff_only && !kind.is_fast_forward() {
+                    bail!("Refusing the {kind} update to {version} because of --ff-only");
+                }
+
+                // Local changes
+                if opts.force {
+                    discard_changes(&subrepo)?;
+                }
+                let stashed =
+                    opts.autostash && stash_changes(&mut subrepo, !opts.ignore_untracked)?;
+                if let (true, Some(journal)) = (stashed, ctx.journal) {
+                    journal.set_stashed(true)?;
+                }
 
                 // Checkout
-                checkout_to_version(&subrepo, version, !opts.no_checkout)?;
+                let checked_out = checkout_to_version(&subrepo, version, !opts.no_checkout);
+                let conflict = match stashed {
+                    true => unstash_changes(&mut subrepo).err(),
+                    false => None,
+                };
+                if let (true, None, Some(journal)) = (stashed, &conflict, ctx.journal) {
+                    journal.set_stashed(false)?;
+                }
+                if let Err(e) = checked_out {
+                    if let Some(conflict) = conflict {
+                        warn!(
+                            "The local changes of {} are kept in its stash: {conflict}",
+                            path.display()
+                        );
+                    }
+                    return Err(e.into());
+                }
 
                 submod.add_finalize()?;
-                Ok(())
-            })();
+                Ok((kind, (used > 0).then(|| candidates[used].clone()), conflict))
+            });
+            summary.record_retries(path, ctx.network.retry_count() - retries_before);
+            if let Ok((kind, url, conflict)) = &result {
+                summary.record_update(path, *kind);
+                if let Some(conflict) = conflict {
+                    summary.record_stash_conflict(path, conflict);
+                }
+                if let Some(url) = url {
+                    summary.record_fallback(path, url);
+                }
+            }
 
             match result {
                 Ok(_) => {
+                    if let Some(journal) = ctx.journal {
+                        journal.finish()?;
+                    }
                     progress.inc(1);
                 }
                 Err(e) => {
                     error!("Failed to update {}: {}", path.display(), e);
+                    if opts.keep_going && !interrupt::is_interrupted() {
+                        if let Err(restore_err) = ctx.tracker.restore(root_repo, submod_name) {
+                            warn!("Failed to restore {}: {}", path.display(), restore_err);
+                        }
+                        summary.record_failure(path, "update", &e);
+                        if let Some(journal) = ctx.journal {
+                            journal.fail()?;
+                        }
+                        progress.inc(1);
+                        continue;
+                    }
                     return Err(e);
                 }
             }
@@ -314,15 +1384,38 @@ fn process_submodule_operations<'a>(
 
     // Handle --sync-selection: remove submodules not in current selection
     if opts.sync_selection {
-        for (path, _submod_name) in removed_repos {
+        for (path, submod_name) in removed_repos {
             if opts.dry_run {
                 progress.println(&format!("[DRY RUN] Would remove {}", path.display()));
             } else {
+                interrupt::check()?;
                 progress.set_message(&format!("Removing {}", path.display()));
+                if let Some(journal) = ctx.journal {
+                    journal.begin(Operation::Remove, path)?;
+                }
 
-                if let Err(e) = remove_submodule(root_repo, path) {
-                    error!("Failed to remove {}: {}", path.display(), e);
-                    return Err(e);
+                let backup = SubmoduleStateTracker::backup_dir(root_repo, submod_name);
+                match remove_submodule(root_repo, path, Some(&backup)) {
+                    Ok(()) => {
+                        if let Some(journal) = ctx.journal {
+                            journal.finish()?;
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to remove {}: {}", path.display(), e);
+                        if !opts.keep_going || interrupt::is_interrupted() {
+                            return Err(e);
+                        }
+                        if let Err(restore_err) =
+                            ctx.tracker.restore_removal(root_repo, submod_name)
+                        {
+                            warn!("Failed to restore {}: {}", path.display(), restore_err);
+                        }
+                        summary.record_failure(path, "remove", &e);
+                        if let Some(journal) = ctx.journal {
+                            journal.fail()?;
+                        }
+                    }
                 }
             }
             progress.inc(1);
@@ -354,7 +1447,7 @@ fn classify_submodules<'a>(
     let mut new_repos: Vec<(&Path, _)> = new_paths
         .map(|&path| (path, &selected_repos[path]))
         .collect();
-    new_repos.sort_unstable_by(|(lp, _), (rp, _)| lp.cmp(rp));
+    new_repos.sort_unstable_by_key(|(path, _)| *path);
 
     let mut updated_repos: Vec<(&Path, _)> = {
         updated_paths
@@ -365,12 +1458,12 @@ fn classify_submodules<'a>(
             })
             .collect()
     };
-    updated_repos.sort_unstable_by(|(lp, _), (rp, _)| lp.cmp(rp));
+    updated_repos.sort_unstable_by_key(|(path, _)| *path);
 
     let mut removed_submods: Vec<(&Path, _)> = removed_paths
         .map(|&path| (path, &submod_names[path]))
         .collect();
-    removed_submods.sort_unstable_by(|(lp, _), (rp, _)| lp.cmp(rp));
+    removed_submods.sort_unstable_by_key(|(path, _)| *path);
 
     (new_repos, updated_repos, removed_submods)
 }
@@ -404,11 +1497,13 @@ mod tests {
             r#type: RepoType::Git,
             url: "https://github.com/test/repo1".parse().unwrap(),
             version: "main".to_string(),
+            fallback_urls: vec![],
         };
         let repo2 = Repo {
             r#type: RepoType::Git,
             url: "https://github.com/test/repo2".parse().unwrap(),
             version: "main".to_string(),
+            fallback_urls: vec![],
         };
         selected_repos.insert(PathBuf::from("prefix/repo1"), &repo1);
         selected_repos.insert(PathBuf::from("prefix/repo2"), &repo2);
@@ -430,6 +1525,7 @@ mod tests {
             r#type: RepoType::Git,
             url: "https://github.com/test/repo1".parse().unwrap(),
             version: "main".to_string(),
+            fallback_urls: vec![],
         };
         selected_repos.insert(PathBuf::from("prefix/repo1"), &repo1);
 
@@ -470,11 +1566,13 @@ mod tests {
             r#type: RepoType::Git,
             url: "https://github.com/test/repo1".parse().unwrap(),
             version: "main".to_string(),
+            fallback_urls: vec![],
         };
         let repo2 = Repo {
             r#type: RepoType::Git,
             url: "https://github.com/test/repo2".parse().unwrap(),
             version: "main".to_string(),
+            fallback_urls: vec![],
         };
         selected_repos.insert(PathBuf::from("prefix/repo1"), &repo1);
         selected_repos.insert(PathBuf::from("prefix/repo2"), &repo2);
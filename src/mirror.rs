@@ -0,0 +1,97 @@
+use crate::{repo_url::RepoUrl, vcs::Repo};
+use git2::Repository;
+use std::path::{Component, Path, PathBuf};
+
+/// A directory of bare mirrors created by `vcs2git mirror`
+///
+/// Each repository is mirrored at a path derived from its URL, such as
+/// `github.com/org/repo.git`, so that the directory can be browsed and
+/// copied into an air-gapped network.
+#[derive(Debug, Clone)]
+pub struct MirrorDir {
+    dir: PathBuf,
+}
+
+impl MirrorDir {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The location of the mirror of a repository.
+    ///
+    /// Different spellings of the same URL share one mirror.
+    pub fn repo_path(&self, url: &RepoUrl) -> PathBuf {
+        let normalized = url.normalized();
+        let relative: PathBuf = Path::new(&normalized)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+        let mut path = self.dir.join(relative);
+        path.as_mut_os_string().push(".git");
+        path
+    }
+
+    /// The URL of the mirror of a repository, usable as a remote URL.
+    pub fn repo_url(&self, url: &RepoUrl) -> RepoUrl {
+        let path = self.repo_path(url);
+        path.to_string_lossy()
+            .parse()
+            .expect("a non-empty path is a valid repository URL")
+    }
+
+    /// Check that the mirrors of the repositories exist and contain
+    /// their versions. Returns a description of each problem found.
+    pub fn check<'a, I>(&self, repos: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+    {
+        let mut problems = Vec::new();
+
+        for (path, repo) in repos {
+            let mirror_path = self.repo_path(&repo.url);
+            let Ok(mirror) = Repository::open_bare(&mirror_path) else {
+                problems.push(format!(
+                    "{}: {} is not mirrored at {}",
+                    path.display(),
+                    repo.url,
+                    mirror_path.display()
+                ));
+                continue;
+            };
+
+            if mirror.revparse_single(&repo.version).is_err() {
+                problems.push(format!(
+                    "{}: version '{}' is missing from the mirror at {}",
+                    path.display(),
+                    repo.version,
+                    mirror_path.display()
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_path_layout() {
+        let mirror = MirrorDir::new(PathBuf::from("/mirror"));
+        let https: RepoUrl = "https://github.com/org/repo.git".parse().unwrap();
+        let scp: RepoUrl = "git@github.com:org/repo".parse().unwrap();
+        let relative: RepoUrl = "../sibling/repo".parse().unwrap();
+
+        assert_eq!(
+            mirror.repo_path(&https),
+            PathBuf::from("/mirror/github.com/org/repo.git")
+        );
+        assert_eq!(mirror.repo_path(&scp), mirror.repo_path(&https));
+        assert_eq!(
+            mirror.repo_path(&relative),
+            PathBuf::from("/mirror/sibling/repo.git")
+        );
+    }
+}
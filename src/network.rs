@@ -0,0 +1,710 @@
+use crate::{cli::NetworkArgs, config::NetworkConfig, interrupt, repo_url::RepoUrl};
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use color_eyre::{
+    eyre::{ensure, Context},
+    Result,
+};
+use git2::{cert::Cert, CertificateCheckStatus, ErrorClass, ErrorCode, ProxyOptions};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// The longest wait between two attempts of a network operation.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Settings applied to every network operation
+#[derive(Debug, Clone, Default)]
+pub struct NetworkOptions {
+    pub proxy: ProxyConfig,
+    /// CA certificates trusted in addition to the system ones.
+    pub ca_bundle: Option<PathBuf>,
+    /// SHA-256 fingerprints of the accepted SSH host keys, by host.
+    pub host_keys: HashMap<String, Vec<String>>,
+    /// Accept any TLS certificate and SSH host key.
+    pub insecure: bool,
+    /// The time allowed for one fetch, unless overridden for the host.
+    pub timeout: Option<Duration>,
+    /// The time allowed for one fetch, by host.
+    pub host_timeouts: HashMap<String, Duration>,
+    /// The time by which all network operations must be done, and the
+    /// overall timeout it was computed from.
+    pub deadline: Option<(Instant, Duration)>,
+    /// How failed network operations are retried.
+    pub retry: RetryPolicy,
+    /// The number of retries made so far.
+    pub retried: Cell<usize>,
+    /// Whether the socket timeouts were set for fetches running in
+    /// parallel, so that single fetches must not change them.
+    pub shared_socket_timeout: bool,
+}
+
+impl NetworkOptions {
+    /// Combine the command line options, which take precedence, the
+    /// configuration file, Git's configuration and the environment.
+    pub fn new(
+        args: &NetworkArgs,
+        config: &NetworkConfig,
+        git_config: &git2::Config,
+    ) -> Result<Self> {
+        let proxy = args
+            .proxy
+            .clone()
+            .or_else(|| config.proxy.clone())
+            .or_else(|| git_config.get_string("http.proxy").ok())
+            .filter(|proxy| !proxy.is_empty());
+
+        let no_proxy = args
+            .no_proxy
+            .iter()
+            .chain(&config.no_proxy)
+            .cloned()
+            .chain(env_list(&["no_proxy", "NO_PROXY"]))
+            .collect();
+
+        let ca_bundle = args
+            .ca_bundle
+            .clone()
+            .or_else(|| config.ca_bundle.clone())
+            .or_else(|| git_config.get_path("http.sslCAInfo").ok());
+
+        let mut host_keys: HashMap<String, Vec<String>> = HashMap::new();
+        let pins = args
+            .host_key
+            .iter()
+            .map(|(host, fingerprint)| (host, fingerprint))
+            .chain(config.host_keys.iter().flat_map(|(host, fingerprints)| {
+                fingerprints
+                    .iter()
+                    .map(move |fingerprint| (host, fingerprint))
+            }));
+        for (host, fingerprint) in pins {
+            ensure!(
+                fingerprint.starts_with("SHA256:"),
+                "Host key fingerprint '{fingerprint}' for {host} must start with 'SHA256:'"
+            );
+            host_keys
+                .entry(host.to_ascii_lowercase())
+                .or_default()
+                .push(fingerprint.trim_end_matches('=').to_string());
+        }
+
+        let timeout = args.timeout.or(config.timeout).map(Duration::from_secs);
+        let host_timeouts = config
+            .host_timeouts
+            .iter()
+            .chain(args.host_timeout.iter().map(|(host, secs)| (host, secs)))
+            .map(|(host, secs)| (host.to_ascii_lowercase(), Duration::from_secs(*secs)))
+            .collect();
+        let deadline = args.total_timeout.or(config.total_timeout).map(|secs| {
+            let total = Duration::from_secs(secs);
+            (Instant::now() + total, total)
+        });
+
+        let defaults = RetryPolicy::default();
+        let retry = RetryPolicy {
+            retries: args.retries.or(config.retries).unwrap_or(defaults.retries),
+            delay: args
+                .retry_delay
+                .or(config.retry_delay)
+                .map_or(defaults.delay, Duration::from_secs),
+        };
+
+        Ok(Self {
+            ca_bundle,
+            host_keys,
+            timeout,
+            host_timeouts,
+            deadline,
+            retry,
+            retried: Cell::new(0),
+            shared_socket_timeout: false,
+            insecure: args.insecure,
+            proxy: ProxyConfig {
+                proxy,
+                // Like curl, ignore HTTP_PROXY, which CGI programs may
+                // set from a request header
+                http_proxy: env_value(&["http_proxy"]),
+                https_proxy: env_value(&["https_proxy", "HTTPS_PROXY"]),
+                all_proxy: env_value(&["all_proxy", "ALL_PROXY"]),
+                no_proxy,
+            },
+        })
+    }
+
+    /// Apply the settings that libgit2 only supports process-wide. Must
+    /// be called before any network operation.
+    pub fn install(&self) -> Result<()> {
+        if let Some(ca_bundle) = &self.ca_bundle {
+            ensure!(
+                ca_bundle.is_file(),
+                "CA bundle {} does not exist",
+                ca_bundle.display()
+            );
+            // SAFETY: called once at startup, before any other thread
+            // uses libgit2. The certificates are added to the system ones.
+            unsafe { git2::opts::set_ssl_cert_file(ca_bundle) }
+                .with_context(|| format!("Failed to load CA bundle {}", ca_bundle.display()))?;
+        }
+
+        if self.insecure {
+            warn!("--insecure: TLS certificates and SSH host keys are NOT verified. Connections can be intercepted.");
+        }
+        Ok(())
+    }
+
+    /// Decide whether to trust the TLS certificate or SSH host key
+    /// presented by a host.
+    ///
+    /// Unless a host key is pinned for the host, libgit2's own checks
+    /// against the CA certificates or `known_hosts` apply.
+    pub fn check_certificate(
+        &self,
+        cert: &Cert,
+        host: &str,
+    ) -> Result<CertificateCheckStatus, git2::Error> {
+        if self.insecure {
+            warn!("Accepting the certificate of {host} without verification (--insecure)");
+            return Ok(CertificateCheckStatus::CertificateOk);
+        }
+
+        let (Some(hostkey), Some(pins)) = (
+            cert.as_hostkey(),
+            self.host_keys.get(&host.to_ascii_lowercase()),
+        ) else {
+            return Ok(CertificateCheckStatus::CertificatePassthrough);
+        };
+
+        let fingerprint = hostkey
+            .hash_sha256()
+            .map(fingerprint)
+            .ok_or_else(|| git2::Error::from_str("The SSH host key has no SHA-256 hash"))?;
+        if pins.contains(&fingerprint) {
+            Ok(CertificateCheckStatus::CertificateOk)
+        } else {
+            Err(git2::Error::from_str(&format!(
+                "The SSH host key of {host} ({fingerprint}) does not match the pinned fingerprints"
+            )))
+        }
+    }
+
+    /// The time allowed for fetching from a URL: the timeout for its
+    /// host, limited by the time left until the overall deadline.
+    ///
+    /// Fails once the overall deadline has passed.
+    pub fn fetch_timeout(&self, url: &str) -> Result<Option<Duration>, git2::Error> {
+        let host = url
+            .parse::<RepoUrl>()
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        let timeout = host
+            .and_then(|host| self.host_timeouts.get(&host).copied())
+            .or(self.timeout);
+
+        let Some((deadline, total)) = self.deadline else {
+            return Ok(timeout);
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(git2::Error::from_str(&format!(
+                "The overall timeout of {}s was exceeded",
+                total.as_secs()
+            )));
+        }
+        Ok(Some(
+            timeout.map_or(remaining, |timeout| timeout.min(remaining)),
+        ))
+    }
+
+    /// The proxy options for fetching from a URL.
+    pub fn proxy_options(&self, url: &str) -> ProxyOptions<'static> {
+        let mut options = ProxyOptions::new();
+        if let Some(proxy) = url
+            .parse::<RepoUrl>()
+            .ok()
+            .and_then(|url| self.proxy.proxy_for(&url).map(str::to_string))
+        {
+            options.url(&proxy);
+        }
+        options
+    }
+
+    /// Run a network operation, retrying it after transient failures
+    /// with exponential backoff.
+    ///
+    /// The operation is passed the number of the attempt, starting at
+    /// 0, so that it can clean up after a failed one. No retry is made
+    /// that would start after the overall deadline.
+    pub fn retry<T>(&self, what: &str, mut operation: impl FnMut(u32) -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            let err = match operation(attempt) {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if attempt >= self.retry.retries || !is_transient(&err) {
+                return Err(err);
+            }
+
+            attempt += 1;
+            let delay = self.retry.delay(attempt);
+            if let Some((deadline, _)) = self.deadline {
+                if Instant::now() + delay >= deadline {
+                    return Err(err);
+                }
+            }
+            warn!(
+                "{what} failed: {err}. Retrying in {}s (retry {attempt} of {}).",
+                delay.as_secs(),
+                self.retry.retries
+            );
+            interrupt::sleep(delay)?;
+            self.retried.set(self.retried.get() + 1);
+        }
+    }
+
+    /// The number of retries made so far.
+    pub fn retry_count(&self) -> usize {
+        self.retried.get()
+    }
+}
+
+/// How often and how patiently failed network operations are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub retries: u32,
+    /// The wait before the first retry, doubled for each further one.
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait before a retry, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Check if a failed network operation may succeed when retried.
+///
+/// Connection problems, timeouts and server errors are transient,
+/// whereas failed authentication, missing repositories and rejected
+/// certificates are not.
+pub fn is_transient(err: &color_eyre::Report) -> bool {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<git2::Error>())
+        .is_some_and(is_transient_git_error)
+}
+
+/// Parts of the messages of libgit2 network and SSH errors that a retry
+/// may fix: dropped connections, unexpected ends of the stream and
+/// timeouts.
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "connection refused",
+    "connection reset",
+    "failed to connect",
+    "eof",
+    "timed out",
+    "timeout",
+];
+
+/// Check if a libgit2 error is transient. See [`is_transient`].
+fn is_transient_git_error(err: &git2::Error) -> bool {
+    match err.code() {
+        ErrorCode::Timeout | ErrorCode::Eof => return true,
+        ErrorCode::Auth | ErrorCode::Certificate | ErrorCode::NotFound => return false,
+        _ => {}
+    }
+
+    match err.class() {
+        // Also failures reported by the server, such as "remote error:
+        // repository not found", and rejected SSH keys
+        ErrorClass::Net | ErrorClass::Ssh => {
+            let message = err.message().to_ascii_lowercase();
+            TRANSIENT_MESSAGES
+                .iter()
+                .any(|transient| message.contains(transient))
+        }
+        // Other OS errors are about local files
+        ErrorClass::Os => err.message().starts_with("failed to connect"),
+        // Only server errors and rate limiting, such as
+        // "unexpected http status code: 503"
+        ErrorClass::Http => {
+            http_status(err.message()).is_some_and(|status| status >= 500 || status == 429)
+        }
+        _ => false,
+    }
+}
+
+/// The HTTP status code reported in a libgit2 error message.
+fn http_status(message: &str) -> Option<u16> {
+    let (_, rest) = message.split_once("status")?;
+    rest.split(|c: char| !c.is_ascii_digit())
+        .find(|word| word.len() == 3)?
+        .parse()
+        .ok()
+}
+
+/// Which HTTP proxy to use for which URL
+///
+/// A proxy set explicitly, through `--proxy`, the configuration file or
+/// Git's `http.proxy`, takes precedence over the `https_proxy`,
+/// `http_proxy` and `all_proxy` environment variables. Hosts matching
+/// `no_proxy` are always contacted directly.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    pub proxy: Option<String>,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub all_proxy: Option<String>,
+    /// Hosts and domains not to use a proxy for, or `*` for all.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// The proxy to use for a URL, if any. Only HTTP and HTTPS URLs are
+    /// fetched through a proxy.
+    pub fn proxy_for(&self, url: &RepoUrl) -> Option<&str> {
+        let scheme_proxy = match url.scheme() {
+            "http" => &self.http_proxy,
+            "https" => &self.https_proxy,
+            _ => return None,
+        };
+
+        if let Some(host) = url.host_str() {
+            if self
+                .no_proxy
+                .iter()
+                .any(|pattern| no_proxy_matches(pattern, host))
+            {
+                return None;
+            }
+        }
+
+        self.proxy
+            .as_deref()
+            .or(scheme_proxy.as_deref())
+            .or(self.all_proxy.as_deref())
+    }
+}
+
+/// Format a SHA-256 host key hash like OpenSSH does.
+fn fingerprint(hash: &[u8; 32]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(hash))
+}
+
+/// Check if a `no_proxy` entry, a host name, a domain such as
+/// `.example.com`, or `*`, matches a host.
+fn no_proxy_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim();
+    if pattern == "*" {
+        return true;
+    }
+
+    // Ports are not distinguished
+    let pattern = pattern.split(':').next().unwrap_or_default();
+    let domain = pattern.trim_start_matches("*.").trim_start_matches('.');
+    if domain.is_empty() {
+        return false;
+    }
+
+    let host = host.to_ascii_lowercase();
+    let domain = domain.to_ascii_lowercase();
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+/// The value of the first of the environment variables that is set and
+/// not empty.
+fn env_value(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// The comma-separated entries of the first of the environment
+/// variables that is set.
+fn env_list(names: &[&str]) -> Vec<String> {
+    env_value(names)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> RepoUrl {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_proxy_for() {
+        let config = ProxyConfig {
+            proxy: None,
+            http_proxy: Some("http://plain-proxy:3128".to_string()),
+            https_proxy: Some("http://tls-proxy:3128".to_string()),
+            all_proxy: None,
+            no_proxy: vec![".internal".to_string(), "localhost".to_string()],
+        };
+
+        assert_eq!(
+            config.proxy_for(&url("https://github.com/org/a.git")),
+            Some("http://tls-proxy:3128")
+        );
+        assert_eq!(
+            config.proxy_for(&url("http://github.com/org/a.git")),
+            Some("http://plain-proxy:3128")
+        );
+        assert_eq!(config.proxy_for(&url("https://git.internal/a.git")), None);
+        assert_eq!(config.proxy_for(&url("http://localhost:8080/a.git")), None);
+        assert_eq!(config.proxy_for(&url("git@github.com:org/a.git")), None);
+        assert_eq!(config.proxy_for(&url("/srv/git/a.git")), None);
+
+        let explicit = ProxyConfig {
+            proxy: Some("http://explicit:8080".to_string()),
+            ..config
+        };
+        assert_eq!(
+            explicit.proxy_for(&url("https://github.com/org/a.git")),
+            Some("http://explicit:8080")
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // The SHA-256 hash of an empty input
+        let hash: [u8; 32] = [
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f,
+            0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b,
+            0x78, 0x52, 0xb8, 0x55,
+        ];
+        assert_eq!(
+            fingerprint(&hash),
+            "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+    }
+
+    #[test]
+    fn test_host_key_pins() {
+        let args = NetworkArgs {
+            host_key: vec![(
+                "GitLab.internal".to_string(),
+                "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".to_string(),
+            )],
+            ..Default::default()
+        };
+        let git_config = git2::Config::new().unwrap();
+        let options = NetworkOptions::new(&args, &NetworkConfig::default(), &git_config).unwrap();
+        assert_eq!(
+            options.host_keys["gitlab.internal"],
+            vec!["SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU".to_string()]
+        );
+
+        let args = NetworkArgs {
+            host_key: vec![("gitlab.internal".to_string(), "MD5:00:11".to_string())],
+            ..Default::default()
+        };
+        assert!(NetworkOptions::new(&args, &NetworkConfig::default(), &git_config).is_err());
+    }
+
+    #[test]
+    fn test_fetch_timeout() {
+        let options = NetworkOptions {
+            timeout: Some(Duration::from_secs(30)),
+            host_timeouts: HashMap::from([("slow.internal".to_string(), Duration::from_secs(600))]),
+            ..Default::default()
+        };
+        assert_eq!(
+            options
+                .fetch_timeout("https://github.com/org/a.git")
+                .unwrap(),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            options
+                .fetch_timeout("git@slow.internal:org/a.git")
+                .unwrap(),
+            Some(Duration::from_secs(600))
+        );
+
+        let limited = NetworkOptions {
+            deadline: Some((
+                Instant::now() + Duration::from_secs(60),
+                Duration::from_secs(60),
+            )),
+            ..options.clone()
+        };
+        let timeout = limited
+            .fetch_timeout("git@slow.internal:org/a.git")
+            .unwrap();
+        assert!(timeout.is_some_and(|timeout| timeout <= Duration::from_secs(60)));
+
+        let expired = NetworkOptions {
+            deadline: Some((Instant::now(), Duration::from_secs(60))),
+            ..options
+        };
+        assert!(expired
+            .fetch_timeout("https://github.com/org/a.git")
+            .is_err());
+    }
+
+    #[test]
+    fn test_no_proxy_matches() {
+        assert!(no_proxy_matches("*", "github.com"));
+        assert!(no_proxy_matches("example.com", "example.com"));
+        assert!(no_proxy_matches("example.com", "git.Example.com"));
+        assert!(no_proxy_matches(".example.com", "git.example.com"));
+        assert!(no_proxy_matches("*.example.com", "git.example.com"));
+        assert!(no_proxy_matches("example.com:443", "example.com"));
+        assert!(!no_proxy_matches("example.com", "notexample.com"));
+        assert!(!no_proxy_matches("", "example.com"));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let transient = [
+            git2::Error::new(ErrorCode::GenericError, ErrorClass::Net, "connection reset"),
+            git2::Error::new(ErrorCode::Timeout, ErrorClass::Net, "timed out"),
+            git2::Error::new(ErrorCode::GenericError, ErrorClass::Net, "early EOF"),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Ssh,
+                "Failed to connect socket: Connection refused",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Ssh,
+                "SSH could not read data: Timeout waiting for status message",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Os,
+                "failed to connect to example.com",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Http,
+                "unexpected http status code: 503",
+            ),
+        ];
+        let permanent = [
+            git2::Error::new(ErrorCode::Auth, ErrorClass::Http, "authentication failure"),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Http,
+                "unexpected http status code: 404",
+            ),
+            git2::Error::new(ErrorCode::NotFound, ErrorClass::Reference, "no such ref"),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Net,
+                "remote error: repository not found",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Net,
+                "remote error: access denied or repository not exported",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Ssh,
+                "Failed to authenticate SSH session: Unable to send userauth-publickey request",
+            ),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Ssh,
+                "Permission denied (publickey)",
+            ),
+            git2::Error::new(ErrorCode::Certificate, ErrorClass::Ssl, "bad certificate"),
+            git2::Error::from_str("The overall timeout of 60s was exceeded"),
+            git2::Error::new(
+                ErrorCode::GenericError,
+                ErrorClass::Os,
+                "failed to resolve path '/nonexistent/repo'",
+            ),
+        ];
+
+        for err in transient {
+            assert!(is_transient(&err.into()));
+        }
+        for err in permanent {
+            let message = err.message().to_string();
+            assert!(!is_transient(&err.into()), "{message}");
+        }
+        assert!(!is_transient(&color_eyre::eyre::eyre!(
+            "not a libgit2 error"
+        )));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            retries: 10,
+            delay: Duration::from_secs(2),
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(4));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(10), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_retry() {
+        let options = NetworkOptions {
+            retry: RetryPolicy {
+                retries: 2,
+                delay: Duration::ZERO,
+            },
+            ..Default::default()
+        };
+        let dropped = || git2::Error::new(ErrorCode::Eof, ErrorClass::Net, "dropped").into();
+
+        // A transient failure is retried
+        let mut attempts = Vec::new();
+        let result = options.retry("Fetching", |attempt| {
+            attempts.push(attempt);
+            if attempt < 1 {
+                Err(dropped())
+            } else {
+                Ok(attempt)
+            }
+        });
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(attempts, [0, 1]);
+        assert_eq!(options.retry_count(), 1);
+
+        // Retries are limited
+        let result: Result<()> = options.retry("Fetching", |_| Err(dropped()));
+        assert!(result.is_err());
+        assert_eq!(options.retry_count(), 3);
+
+        // A permanent failure is not retried
+        let result: Result<()> = options.retry("Fetching", |_| {
+            Err(git2::Error::new(ErrorCode::Auth, ErrorClass::Ssh, "denied").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(options.retry_count(), 3);
+    }
+}
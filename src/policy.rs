@@ -0,0 +1,299 @@
+use crate::{
+    repo_url::RepoUrl,
+    vcs::{Repo, VersionKind},
+};
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// The policy file looked up in the superproject when `--policy` is
+/// not given.
+pub const DEFAULT_POLICY_FILE: &str = ".vcs2git-policy.toml";
+
+/// Restrictions on which repositories a workspace may pull
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Hosts, optionally followed by an organization such as
+    /// `github.com/autowarefoundation`, that URLs must point to.
+    /// URLs without a host, such as `file://` URLs, are not affected.
+    #[serde(default)]
+    pub allowed_hosts: Option<Vec<String>>,
+
+    /// URL schemes that may be used.
+    #[serde(default)]
+    pub allowed_schemes: Option<Vec<String>>,
+
+    /// How precisely versions must be pinned.
+    #[serde(default)]
+    pub pinning: Pinning,
+
+    /// Repository URLs that must never be used, also as fallbacks.
+    #[serde(default)]
+    pub forbidden_repositories: Vec<RepoUrl>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pinning {
+    /// Versions must be full commit hashes.
+    Sha,
+    /// Versions must be full commit hashes or tags.
+    Tag,
+    /// Any version is accepted.
+    #[default]
+    Any,
+}
+
+/// A repository entry that does not comply with the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub path: PathBuf,
+    /// The name of the violated policy key.
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: violates '{}': {}",
+            self.path.display(),
+            self.rule,
+            self.message
+        )
+    }
+}
+
+impl Policy {
+    /// Load a policy file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse policy file {}", path.display()))
+    }
+
+    /// Load the policy file given on the command line, or the default
+    /// policy file if it exists.
+    pub fn discover(path: Option<&Path>) -> Result<Option<Self>> {
+        match path {
+            Some(path) => Ok(Some(Self::load(path)?)),
+            None => {
+                let path = Path::new(DEFAULT_POLICY_FILE);
+                if path.exists() {
+                    Ok(Some(Self::load(path)?))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    /// Check repository entries against the policy and return every
+    /// violation found.
+    pub fn check<'a, I>(&self, repos: I) -> Vec<PolicyViolation>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+    {
+        self.check_resolved(repos.into_iter().map(|(path, repo)| (path, repo, vec![])))
+    }
+
+    /// Check repository entries against the policy, together with the
+    /// URLs they are recorded and fetched with after rewriting, style
+    /// conversion and configured fallbacks.
+    pub fn check_resolved<'a, I>(&self, repos: I) -> Vec<PolicyViolation>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a Repo, Vec<RepoUrl>)>,
+    {
+        let forbidden: HashSet<String> = self
+            .forbidden_repositories
+            .iter()
+            .map(RepoUrl::normalized)
+            .collect();
+
+        let mut violations = Vec::new();
+
+        for (path, repo, resolved) in repos {
+            let mut report = |rule, message| {
+                violations.push(PolicyViolation {
+                    path: path.to_path_buf(),
+                    rule,
+                    message,
+                })
+            };
+
+            // Each URL is reported once, even if rewriting leaves it as is
+            let mut seen = HashSet::new();
+            let urls = repo
+                .urls()
+                .chain(&resolved)
+                .filter(|url| seen.insert(url.as_str()));
+
+            for url in urls {
+                if let Some(schemes) = &self.allowed_schemes {
+                    let scheme = url.scheme();
+                    if !schemes.iter().any(|allowed| allowed == scheme) {
+                        report(
+                            "allowed_schemes",
+                            format!("scheme '{scheme}' of {url} is not allowed"),
+                        );
+                    }
+                }
+
+                if let (Some(hosts), Some(host)) = (&self.allowed_hosts, url.host_str()) {
+                    if !hosts
+                        .iter()
+                        .any(|allowed| host_matches(allowed, host, url.path()))
+                    {
+                        report("allowed_hosts", format!("{url} is not on an allowed host"));
+                    }
+                }
+
+                if forbidden.contains(&url.normalized()) {
+                    report("forbidden_repositories", format!("{url} is forbidden"));
+                }
+            }
+
+            let kind = VersionKind::of(&repo.version);
+            let is_pinned = match self.pinning {
+                Pinning::Sha => kind == VersionKind::Commit,
+                Pinning::Tag => matches!(kind, VersionKind::Commit | VersionKind::Tag),
+                Pinning::Any => true,
+            };
+            if !is_pinned {
+                report(
+                    "pinning",
+                    format!(
+                        "version '{}' is not pinned to a {}",
+                        repo.version,
+                        match self.pinning {
+                            Pinning::Sha => "full commit hash",
+                            _ => "commit hash or tag",
+                        }
+                    ),
+                );
+            }
+        }
+
+        violations
+    }
+}
+
+/// Check if an `allowed_hosts` entry, either `host` or `host/org`,
+/// matches a URL host and path.
+fn host_matches(allowed: &str, host: &str, url_path: &str) -> bool {
+    let (allowed_host, allowed_org) = match allowed.split_once('/') {
+        Some((host, org)) => (host, Some(org.trim_matches('/'))),
+        None => (allowed, None),
+    };
+
+    if !allowed_host.eq_ignore_ascii_case(host) {
+        return false;
+    }
+
+    match allowed_org {
+        Some(org) => url_path
+            .trim_start_matches('/')
+            .strip_prefix(org)
+            .is_some_and(|rest| rest.starts_with('/')),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::RepoType;
+
+    fn repo(url: &str, version: &str) -> Repo {
+        Repo {
+            r#type: RepoType::Git,
+            url: url.parse().unwrap(),
+            version: version.to_string(),
+            fallback_urls: vec![],
+        }
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let policy: Policy = toml::from_str(
+            r#"
+allowed_hosts = ["github.com/autowarefoundation", "gitlab.internal"]
+allowed_schemes = ["https", "file"]
+pinning = "tag"
+forbidden_repositories = ["https://gitlab.internal/legacy/old.git"]
+"#,
+        )
+        .unwrap();
+
+        let ok = repo("https://github.com/autowarefoundation/a.git", "1.0.0");
+        let other_org = repo("https://github.com/someone/a.git", "1.0.0");
+        let ssh = repo("ssh://git@gitlab.internal/team/b.git", "1.0.0");
+        let floating = repo("https://gitlab.internal/team/c.git", "main");
+        let forbidden = repo("https://gitlab.internal/legacy/old", "1.0.0");
+        let local = repo("file:///srv/git/d.git", "1.0.0");
+
+        let entries = [
+            (Path::new("ok"), &ok),
+            (Path::new("other_org"), &other_org),
+            (Path::new("ssh"), &ssh),
+            (Path::new("floating"), &floating),
+            (Path::new("forbidden"), &forbidden),
+            (Path::new("local"), &local),
+        ];
+        let violations: Vec<_> = policy
+            .check(entries)
+            .into_iter()
+            .map(|violation| (violation.path, violation.rule))
+            .collect();
+
+        assert_eq!(
+            violations,
+            vec![
+                (PathBuf::from("other_org"), "allowed_hosts"),
+                (PathBuf::from("ssh"), "allowed_schemes"),
+                (PathBuf::from("floating"), "pinning"),
+                (PathBuf::from("forbidden"), "forbidden_repositories"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_policy_rejects_unknown_keys() {
+        assert!(toml::from_str::<Policy>("allowed_host = []").is_err());
+    }
+
+    #[test]
+    fn test_policy_rejects_malformed_urls() {
+        assert!(toml::from_str::<Policy>("forbidden_repositories = [\"\"]").is_err());
+        assert!(toml::from_str::<Policy>("forbidden_repositories = [\"https://\"]").is_err());
+    }
+
+    #[test]
+    fn test_policy_checks_resolved_urls() {
+        let policy: Policy = toml::from_str(
+            r#"
+allowed_hosts = ["github.com"]
+forbidden_repositories = ["git@github.com:legacy/old.git"]
+"#,
+        )
+        .unwrap();
+
+        let entry = repo("https://github.com/team/a.git", "main");
+        let rewritten = "https://gitlab.example.com/team/a.git".parse().unwrap();
+        let fallback = "https://github.com/legacy/old.git".parse().unwrap();
+
+        let violations: Vec<_> = policy
+            .check_resolved([(Path::new("a"), &entry, vec![rewritten, fallback])])
+            .into_iter()
+            .map(|violation| violation.rule)
+            .collect();
+        assert_eq!(violations, vec!["allowed_hosts", "forbidden_repositories"]);
+    }
+}
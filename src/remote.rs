@@ -0,0 +1,370 @@
+use crate::{
+    cli::Opts,
+    config::{Config, RecordUrl, UrlRewrite, UrlStyle},
+    mirror::MirrorDir,
+    repo_url::RepoUrl,
+    vcs::Repo,
+};
+use color_eyre::{eyre::Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Hosts converted by `--url-style` without configuration.
+const KNOWN_FORGES: &[&str] = &["github.com", "gitlab.com", "bitbucket.org"];
+
+/// The URLs used for one repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedUrls {
+    /// The URL written to `.gitmodules`.
+    pub record: RepoUrl,
+    /// The URL fetched from.
+    pub fetch: RepoUrl,
+    /// URLs fetched from, in order, when fetching from `fetch` fails.
+    pub fallbacks: Vec<RepoUrl>,
+}
+
+impl ResolvedUrls {
+    /// The URL fetched from followed by the fallback URLs.
+    pub fn candidates(&self) -> Vec<RepoUrl> {
+        std::iter::once(&self.fetch)
+            .chain(&self.fallbacks)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Decides which URLs are recorded and fetched for each repository
+#[derive(Debug)]
+pub struct UrlResolver {
+    rules: Vec<RewriteRule>,
+    record_url: RecordUrl,
+    url_style: UrlStyle,
+    forge_hosts: Vec<String>,
+    /// Fallback URLs from the configuration, keyed by normalized URL.
+    fallbacks: HashMap<String, Vec<RepoUrl>>,
+    /// Mirrors fetched from instead of any other URL when offline.
+    mirror: Option<MirrorDir>,
+}
+
+#[derive(Debug)]
+enum RewriteRule {
+    Prefix { instead_of: String, base: String },
+    Regex { regex: Regex, replacement: String },
+}
+
+impl UrlResolver {
+    /// Create a resolver from rewrite rules.
+    ///
+    /// For each URL, the first matching rule is applied.
+    pub fn new(rewrites: &[UrlRewrite], record_url: RecordUrl) -> Result<Self> {
+        let rules = rewrites
+            .iter()
+            .map(|rewrite| match rewrite {
+                UrlRewrite::Prefix { instead_of, base } => Ok(RewriteRule::Prefix {
+                    instead_of: instead_of.clone(),
+                    base: base.clone(),
+                }),
+                UrlRewrite::Regex { regex, replacement } => Ok(RewriteRule::Regex {
+                    regex: Regex::new(regex)
+                        .with_context(|| format!("Invalid URL rewrite pattern '{regex}'"))?,
+                    replacement: replacement.clone(),
+                }),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            rules,
+            record_url,
+            url_style: UrlStyle::Keep,
+            forge_hosts: KNOWN_FORGES.iter().map(|host| host.to_string()).collect(),
+            fallbacks: HashMap::new(),
+            mirror: None,
+        })
+    }
+
+    /// Add fallback URLs for repositories, keyed by their URL.
+    pub fn with_fallbacks<'a, I>(mut self, fallbacks: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a String, &'a Vec<RepoUrl>)>,
+    {
+        for (url, urls) in fallbacks {
+            let url: RepoUrl = url.parse()?;
+            self.fallbacks
+                .entry(url.normalized())
+                .or_default()
+                .extend(urls.iter().cloned());
+        }
+        Ok(self)
+    }
+
+    /// Convert URLs on Git forges to the given style before rewriting.
+    pub fn with_url_style(mut self, url_style: UrlStyle, extra_forges: &[String]) -> Self {
+        self.url_style = url_style;
+        self.forge_hosts.extend(extra_forges.iter().cloned());
+        self
+    }
+
+    /// Fetch every repository from its mirror and nothing else.
+    pub fn with_mirror(mut self, mirror: MirrorDir) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
+    /// Check if every repository is fetched from the mirror.
+    pub fn is_offline(&self) -> bool {
+        self.mirror.is_some()
+    }
+
+    /// Create a resolver from the configuration file alone.
+    pub fn from_config_file(config: &Config) -> Result<Self> {
+        let record_url = config.record_url.unwrap_or_default();
+        let url_style = config.url_style.unwrap_or_default();
+
+        Self::new(&config.url_rewrites, record_url)?
+            .with_url_style(url_style, &config.forge_hosts)
+            .with_fallbacks(&config.fallback_urls)
+    }
+
+    /// Create a resolver from command line options, which take
+    /// precedence, and the configuration file.
+    pub fn from_config(opts: &Opts, config: &Config) -> Result<Self> {
+        let rewrites: Vec<UrlRewrite> = opts
+            .url_rewrites()
+            .into_iter()
+            .chain(config.url_rewrites.iter().cloned())
+            .collect();
+        let record_url = opts.record_url.or(config.record_url).unwrap_or_default();
+        let url_style = opts.url_style.or(config.url_style).unwrap_or_default();
+
+        let resolver = Self::new(&rewrites, record_url)?
+            .with_url_style(url_style, &config.forge_hosts)
+            .with_fallbacks(&config.fallback_urls)?;
+
+        Ok(match (&opts.mirror_dir, opts.offline) {
+            (Some(dir), true) => resolver.with_mirror(MirrorDir::new(dir.clone())),
+            _ => resolver,
+        })
+    }
+
+    /// Convert the URL to the preferred style if it is on a Git forge.
+    pub fn restyle(&self, url: &RepoUrl) -> RepoUrl {
+        let is_forge = url.host_str().is_some_and(|host| {
+            self.forge_hosts
+                .iter()
+                .any(|forge| forge.eq_ignore_ascii_case(host))
+        });
+        if !is_forge {
+            return url.clone();
+        }
+
+        let converted = match self.url_style {
+            UrlStyle::Ssh => url.to_ssh(),
+            UrlStyle::Https => url.to_https(),
+            UrlStyle::Keep => None,
+        };
+        converted.unwrap_or_else(|| url.clone())
+    }
+
+    /// Apply the first matching rewrite rule to the URL.
+    pub fn rewrite(&self, url: &RepoUrl) -> Result<RepoUrl> {
+        let original = url.as_str();
+
+        let rewritten = self.rules.iter().find_map(|rule| match rule {
+            RewriteRule::Prefix { instead_of, base } => original
+                .strip_prefix(instead_of.as_str())
+                .map(|rest| format!("{base}{rest}")),
+            RewriteRule::Regex { regex, replacement } => regex
+                .is_match(original)
+                .then(|| regex.replace(original, replacement.as_str()).into_owned()),
+        });
+
+        match rewritten {
+            Some(rewritten) => rewritten
+                .parse()
+                .with_context(|| format!("URL {original} was rewritten to an invalid URL")),
+            None => Ok(url.clone()),
+        }
+    }
+
+    /// Resolve the recorded and fetched URLs of a repository.
+    ///
+    /// Fallback URLs are used as written, without style conversion or
+    /// rewriting. With a mirror, only the mirror is fetched from.
+    pub fn resolve(&self, repo: &Repo) -> Result<ResolvedUrls> {
+        let url = self.restyle(&repo.url);
+        let rewritten = self.rewrite(&url)?;
+
+        let record = match self.record_url {
+            RecordUrl::Original => url,
+            RecordUrl::Rewritten => rewritten.clone(),
+        };
+
+        if let Some(mirror) = &self.mirror {
+            return Ok(ResolvedUrls {
+                record,
+                fetch: mirror.repo_url(&repo.url),
+                fallbacks: vec![],
+            });
+        }
+
+        let fetch = rewritten;
+        let mut fallbacks = repo.fallback_urls.clone();
+        if let Some(urls) = self.fallbacks.get(&repo.url.normalized()) {
+            fallbacks.extend(urls.iter().cloned());
+        }
+
+        Ok(ResolvedUrls {
+            record,
+            fetch,
+            fallbacks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::RepoType;
+
+    fn url(text: &str) -> RepoUrl {
+        text.parse().unwrap()
+    }
+
+    fn repo(text: &str) -> Repo {
+        Repo {
+            r#type: RepoType::Git,
+            url: url(text),
+            version: "main".to_string(),
+            fallback_urls: vec![],
+        }
+    }
+
+    #[test]
+    fn test_rewrite_rules() {
+        let resolver = UrlResolver::new(
+            &[
+                UrlRewrite::Prefix {
+                    instead_of: "https://github.com/".to_string(),
+                    base: "https://gitlab.internal/mirror/".to_string(),
+                },
+                UrlRewrite::Regex {
+                    regex: "^git@github\\.com:(.*)$".to_string(),
+                    replacement: "https://gitlab.internal/mirror/$1".to_string(),
+                },
+            ],
+            RecordUrl::Original,
+        )
+        .unwrap();
+
+        assert_eq!(
+            resolver
+                .rewrite(&url("https://github.com/org/a.git"))
+                .unwrap(),
+            url("https://gitlab.internal/mirror/org/a.git")
+        );
+        assert_eq!(
+            resolver.rewrite(&url("git@github.com:org/b.git")).unwrap(),
+            url("https://gitlab.internal/mirror/org/b.git")
+        );
+        assert_eq!(
+            resolver.rewrite(&url("https://example.com/c.git")).unwrap(),
+            url("https://example.com/c.git")
+        );
+    }
+
+    #[test]
+    fn test_resolve_record_url() {
+        let rewrites = [UrlRewrite::Prefix {
+            instead_of: "https://github.com/".to_string(),
+            base: "file:///srv/mirror/".to_string(),
+        }];
+        let original = repo("https://github.com/org/a.git");
+
+        let resolver = UrlResolver::new(&rewrites, RecordUrl::Original).unwrap();
+        let urls = resolver.resolve(&original).unwrap();
+        assert_eq!(urls.record, original.url);
+        assert_eq!(urls.fetch, url("file:///srv/mirror/org/a.git"));
+
+        let resolver = UrlResolver::new(&rewrites, RecordUrl::Rewritten).unwrap();
+        let urls = resolver.resolve(&original).unwrap();
+        assert_eq!(urls.record, url("file:///srv/mirror/org/a.git"));
+    }
+
+    #[test]
+    fn test_resolve_offline() {
+        let mut original = repo("https://github.com/org/a.git");
+        original.fallback_urls = vec![url("https://gitlab.com/org/a.git")];
+
+        let resolver = UrlResolver::new(&[], RecordUrl::Original)
+            .unwrap()
+            .with_mirror(MirrorDir::new("/srv/mirror".into()));
+        let urls = resolver.resolve(&original).unwrap();
+        assert_eq!(urls.record, original.url);
+        assert_eq!(
+            urls.candidates(),
+            vec![url("/srv/mirror/github.com/org/a.git")]
+        );
+    }
+
+    #[test]
+    fn test_url_style() {
+        let resolver = UrlResolver::new(&[], RecordUrl::Original)
+            .unwrap()
+            .with_url_style(UrlStyle::Ssh, &["git.internal".to_string()]);
+
+        let urls = resolver
+            .resolve(&repo("https://github.com/org/a.git"))
+            .unwrap();
+        assert_eq!(urls.record, url("git@github.com:org/a.git"));
+        assert_eq!(urls.fetch, urls.record);
+
+        let urls = resolver
+            .resolve(&repo("https://git.internal/team/b.git"))
+            .unwrap();
+        assert_eq!(urls.record, url("git@git.internal:team/b.git"));
+
+        // Unknown hosts are left alone
+        let urls = resolver
+            .resolve(&repo("https://example.com/c.git"))
+            .unwrap();
+        assert_eq!(urls.record, url("https://example.com/c.git"));
+
+        let resolver = UrlResolver::new(&[], RecordUrl::Original)
+            .unwrap()
+            .with_url_style(UrlStyle::Https, &[]);
+        let urls = resolver.resolve(&repo("git@gitlab.com:group/d")).unwrap();
+        assert_eq!(urls.record, url("https://gitlab.com/group/d"));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let configured = vec![url("file:///srv/config-mirror/a.git")];
+        let fallbacks = [("https://github.com/org/a".to_string(), configured)];
+        let resolver = UrlResolver::new(&[], RecordUrl::Original)
+            .unwrap()
+            .with_fallbacks(fallbacks.iter().map(|(url, urls)| (url, urls)))
+            .unwrap();
+
+        let mut entry = repo("https://github.com/org/a.git");
+        entry.fallback_urls = vec![url("file:///srv/mirror/a.git")];
+
+        let urls = resolver.resolve(&entry).unwrap();
+        assert_eq!(
+            urls.candidates(),
+            vec![
+                url("https://github.com/org/a.git"),
+                url("file:///srv/mirror/a.git"),
+                url("file:///srv/config-mirror/a.git"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let rewrites = [UrlRewrite::Regex {
+            regex: "(".to_string(),
+            replacement: String::new(),
+        }];
+        assert!(UrlResolver::new(&rewrites, RecordUrl::Original).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+use url::Url;
+
+/// A repository location as written in a repos file
+///
+/// Besides regular URLs, it accepts the scp-like form
+/// `[user@]host:path` used by Git for SSH remotes and plain local
+/// paths. The original spelling is kept so that it can be written to
+/// `.gitmodules` unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepoUrl {
+    raw: String,
+    kind: UrlKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum UrlKind {
+    /// A URL with an explicit scheme, such as `https://` or `ssh://`.
+    Url(Url),
+    /// The scp-like `[user@]host:path` form, always meaning SSH.
+    Scp { host: String, path: String },
+    /// A path on the local file system.
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRepoUrlError {
+    input: String,
+    reason: String,
+}
+
+impl fmt::Display for ParseRepoUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid repository URL '{}': {}",
+            self.input, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseRepoUrlError {}
+
+impl RepoUrl {
+    /// The URL exactly as it was written.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// The URL scheme. Scp-like URLs report `ssh` and local paths
+    /// report `file`.
+    pub fn scheme(&self) -> &str {
+        match &self.kind {
+            UrlKind::Url(url) => url.scheme(),
+            UrlKind::Scp { .. } => "ssh",
+            UrlKind::Local => "file",
+        }
+    }
+
+    /// The host name, if the URL has one.
+    pub fn host_str(&self) -> Option<&str> {
+        match &self.kind {
+            UrlKind::Url(url) => url.host_str(),
+            UrlKind::Scp { host, .. } => Some(host),
+            UrlKind::Local => None,
+        }
+    }
+
+    /// The path of the repository on its host, starting with `/`
+    /// unless it is a relative local path.
+    pub fn path(&self) -> &str {
+        match &self.kind {
+            UrlKind::Url(url) => url.path(),
+            UrlKind::Scp { path, .. } => path,
+            UrlKind::Local => &self.raw,
+        }
+    }
+
+    /// Spell the URL in the scp-like SSH form `git@host:path`.
+    ///
+    /// Returns `None` for URLs that cannot be converted, such as local
+    /// paths or URLs with an explicit port.
+    pub fn to_ssh(&self) -> Option<Self> {
+        let (host, path) = self.forge_location()?;
+        let ssh = format!("git@{host}:{path}");
+        ssh.parse().ok()
+    }
+
+    /// Spell the URL in the `https://host/path` form.
+    ///
+    /// Returns `None` for URLs that cannot be converted, such as local
+    /// paths or URLs with an explicit port.
+    pub fn to_https(&self) -> Option<Self> {
+        let (host, path) = self.forge_location()?;
+        let https = format!("https://{host}/{path}");
+        https.parse().ok()
+    }
+
+    /// The host and the path without leading slash of a URL hosted on
+    /// a Git forge.
+    fn forge_location(&self) -> Option<(&str, &str)> {
+        match &self.kind {
+            UrlKind::Url(url) => {
+                let is_remote = matches!(url.scheme(), "https" | "http" | "ssh" | "git");
+                if !is_remote || url.port().is_some() {
+                    return None;
+                }
+                Some((url.host_str()?, url.path().trim_start_matches('/')))
+            }
+            UrlKind::Scp { host, path } => Some((host, path.trim_start_matches('/'))),
+            UrlKind::Local => None,
+        }
+    }
+
+    /// Reduce the URL to a form where different spellings of the same
+    /// repository compare equal, e.g. the scp-like and `https://` forms
+    /// of a GitHub repository.
+    pub fn normalized(&self) -> String {
+        let host = self.host_str().unwrap_or_default().to_ascii_lowercase();
+        let path = self.path().trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        format!("{host}{path}")
+    }
+}
+
+impl FromStr for RepoUrl {
+    type Err = ParseRepoUrlError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| ParseRepoUrlError {
+            input: input.to_string(),
+            reason,
+        };
+
+        if input.is_empty() {
+            return Err(error("the URL is empty".to_string()));
+        }
+
+        let kind = if input.contains("://") {
+            UrlKind::Url(Url::parse(input).map_err(|err| error(err.to_string()))?)
+        } else if let Some((host, path)) = split_scp(input) {
+            if path.is_empty() {
+                return Err(error("the repository path is empty".to_string()));
+            }
+            let path = if path.starts_with('/') {
+                path.to_string()
+            } else {
+                format!("/{path}")
+            };
+            UrlKind::Scp {
+                host: host.to_string(),
+                path,
+            }
+        } else {
+            UrlKind::Local
+        };
+
+        Ok(Self {
+            raw: input.to_string(),
+            kind,
+        })
+    }
+}
+
+/// Split an scp-like `[user@]host:path` into host and path, following
+/// Git's rule that a colon before the first slash marks this form.
+fn split_scp(input: &str) -> Option<(&str, &str)> {
+    let colon = input.find(':')?;
+    if input[..colon].contains('/') {
+        return None;
+    }
+
+    let user_host = &input[..colon];
+    let host = match user_host.rsplit_once('@') {
+        Some((_user, host)) => host,
+        None => user_host,
+    };
+
+    // A single letter is a Windows drive such as `C:`.
+    if host.is_empty() || (host.len() == 1 && user_host.len() == 1) {
+        return None;
+    }
+
+    Some((host, &input[colon + 1..]))
+}
+
+impl fmt::Display for RepoUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Serialize for RepoUrl {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for RepoUrl {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scp_url() {
+        let url: RepoUrl = "git@github.com:org/repo.git".parse().unwrap();
+        assert_eq!(url.scheme(), "ssh");
+        assert_eq!(url.host_str(), Some("github.com"));
+        assert_eq!(url.path(), "/org/repo.git");
+        assert_eq!(url.as_str(), "git@github.com:org/repo.git");
+    }
+
+    #[test]
+    fn test_parse_other_forms() {
+        let url: RepoUrl = "ssh://git@github.com/org/repo.git".parse().unwrap();
+        assert_eq!(url.scheme(), "ssh");
+        assert_eq!(url.host_str(), Some("github.com"));
+
+        let url: RepoUrl = "https://github.com/org/repo".parse().unwrap();
+        assert_eq!(url.scheme(), "https");
+
+        let url: RepoUrl = "file:///srv/git/repo.git".parse().unwrap();
+        assert_eq!(url.scheme(), "file");
+        assert_eq!(url.host_str(), None);
+
+        let url: RepoUrl = "/srv/git/repo.git".parse().unwrap();
+        assert_eq!(url.scheme(), "file");
+        assert_eq!(url.path(), "/srv/git/repo.git");
+
+        let url: RepoUrl = "../sibling/repo".parse().unwrap();
+        assert_eq!(url.scheme(), "file");
+
+        assert!("https://[invalid".parse::<RepoUrl>().is_err());
+        assert!("".parse::<RepoUrl>().is_err());
+    }
+
+    #[test]
+    fn test_normalized_forms_match() {
+        let forms = [
+            "git@github.com:org/repo.git",
+            "ssh://git@github.com/org/repo.git",
+            "https://github.com/org/repo",
+            "https://GitHub.com/org/repo/",
+        ];
+        for form in forms {
+            let url: RepoUrl = form.parse().unwrap();
+            assert_eq!(url.normalized(), "github.com/org/repo", "{form}");
+        }
+    }
+
+    #[test]
+    fn test_convert_style() {
+        let https: RepoUrl = "https://github.com/org/repo.git".parse().unwrap();
+        let scp: RepoUrl = "git@github.com:org/repo.git".parse().unwrap();
+        let ssh: RepoUrl = "ssh://git@github.com/org/repo.git".parse().unwrap();
+
+        assert_eq!(https.to_ssh(), Some(scp.clone()));
+        assert_eq!(ssh.to_ssh(), Some(scp.clone()));
+        assert_eq!(scp.to_https(), Some(https.clone()));
+        assert_eq!(ssh.to_https(), Some(https));
+
+        let with_port: RepoUrl = "ssh://git@example.com:2222/repo.git".parse().unwrap();
+        assert_eq!(with_port.to_https(), None);
+        let local: RepoUrl = "/srv/git/repo.git".parse().unwrap();
+        assert_eq!(local.to_ssh(), None);
+    }
+
+    #[test]
+    fn test_deserialize_keeps_spelling() {
+        let url: RepoUrl = serde_yaml::from_str("git@gitlab.com:group/sub/repo").unwrap();
+        assert_eq!(serde_yaml::to_string(&url).unwrap().trim(), url.as_str());
+    }
+}
@@ -1,22 +1,102 @@
+use crate::git_ops::recreate_worktree;
 use color_eyre::{eyre::eyre, Result};
-use git2::Repository;
-use std::{collections::HashMap, path::PathBuf};
+use git2::{Config, IndexEntry, IndexTime, Oid, Repository};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 use tracing::info;
 
+/// The file mode of submodule entries in the index.
+const GITLINK_MODE: u32 = 0o160000;
+
+/// A configuration value pattern that matches no value.
+const MATCH_NO_VALUE: &str = "$.^";
+
+/// Where removed submodules are kept until the run completes, relative
+/// to the Git directory of the superproject.
+const BACKUP_DIR: &str = "vcs2git/removed";
+
 /// Tracks original submodule states for rollback
-#[derive(Debug)]
+///
+/// Besides the commit of each submodule, the tracker snapshots
+/// `.gitmodules`, the `submodule.*` sections of `.git/config` and the
+/// submodule entries of the index, and restores them exactly.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SubmoduleStateTracker {
     original_states: HashMap<String, SubmoduleState>,
+    /// The content of `.gitmodules`, if it existed.
+    #[serde(default)]
+    gitmodules: Option<String>,
+    /// The `submodule.*` entries of `.gitmodules`, in order.
+    #[serde(default)]
+    gitmodules_entries: Vec<(String, String)>,
+    /// The `submodule.*` entries of `.git/config`, in order.
+    #[serde(default)]
+    config_entries: Vec<(String, String)>,
+    /// The index entries of the submodules and `.gitmodules`.
+    #[serde(default)]
+    index_entries: Vec<IndexRecord>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubmoduleState {
-    #[allow(dead_code)]
     name: String,
     path: PathBuf,
-    commit: git2::Oid,
-    #[allow(dead_code)]
+    #[serde(serialize_with = "serialize_oid", deserialize_with = "deserialize_oid")]
+    commit: Oid,
     url: String,
+    /// The URL of the `origin` remote of the submodule repository.
+    #[serde(default)]
+    remote_url: Option<String>,
+}
+
+/// An index entry of the superproject
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexRecord {
+    path: String,
+    mode: u32,
+    #[serde(serialize_with = "serialize_oid", deserialize_with = "deserialize_oid")]
+    id: Oid,
+}
+
+impl IndexRecord {
+    fn entry(&self) -> IndexEntry {
+        IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: self.mode,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: self.id,
+            flags: 0,
+            flags_extended: 0,
+            path: self.path.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl SubmoduleState {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn commit(&self) -> Oid {
+        self.commit
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
 }
 
 impl SubmoduleStateTracker {
@@ -29,51 +109,311 @@ impl SubmoduleStateTracker {
                 .name()
                 .ok_or_else(|| eyre!("Submodule without name"))?
                 .to_string();
+            // Submodules that are not initialized are left alone by
+            // the run
+            let Some(commit) = submodule.workdir_id() else {
+                continue;
+            };
+            let remote_url = submodule.open().ok().and_then(|sub_repo| {
+                let remote = sub_repo.find_remote("origin").ok()?;
+                remote.url().map(str::to_string)
+            });
 
             let state = SubmoduleState {
                 name: name.clone(),
                 path: submodule.path().to_path_buf(),
-                commit: submodule
-                    .workdir_id()
-                    .ok_or_else(|| eyre!("Submodule {} has no workdir commit", name))?,
+                commit,
                 url: submodule
                     .url()
                     .ok_or_else(|| eyre!("Submodule {} has no URL", name))?
                     .to_string(),
+                remote_url,
             };
 
             original_states.insert(name, state);
         }
 
-        Ok(Self { original_states })
+        let gitmodules_path = gitmodules_path(repo)?;
+        let (gitmodules, gitmodules_entries) = if gitmodules_path.exists() {
+            (
+                Some(fs::read_to_string(&gitmodules_path)?),
+                submodule_config_entries(&Config::open(&gitmodules_path)?)?,
+            )
+        } else {
+            (None, vec![])
+        };
+
+        Ok(Self {
+            original_states,
+            gitmodules,
+            gitmodules_entries,
+            config_entries: submodule_config_entries(&local_config(repo)?)?,
+            index_entries: submodule_index_entries(repo)?,
+        })
+    }
+
+    /// The submodules before the run.
+    pub fn states(&self) -> impl Iterator<Item = &SubmoduleState> {
+        self.original_states.values()
     }
 
-    /// Restore all submodules to their original commits
+    /// The directory a removed submodule is moved to, so that the
+    /// removal can be rolled back.
+    pub fn backup_dir(repo: &Repository, name: &str) -> PathBuf {
+        repo.path().join(BACKUP_DIR).join(name)
+    }
+
+    /// Restore `.gitmodules`, the configuration, the index, removed
+    /// submodules and the original commits of all submodules
     pub fn rollback(&self, repo: &Repository) -> Result<()> {
         info!("Rolling back submodule changes...");
 
-        for (name, state) in &self.original_states {
-            info!("  Restoring {} to commit {}", name, state.commit);
+        self.restore_gitmodules(repo)?;
+        self.restore_config(repo)?;
 
-            let submodule = repo.find_submodule(name)?;
-            let sub_repo = submodule.open()?;
+        for state in self.original_states.values() {
+            restore_removed(repo, state)?;
+        }
+        for state in self.original_states.values() {
+            restore_checkout(repo, state)?;
+        }
+
+        self.restore_index(repo)?;
+        Self::discard_backups(repo)?;
 
-            // Checkout the original commit
-            let obj = sub_repo.find_object(state.commit, None)?;
-            sub_repo.checkout_tree(&obj, None)?;
-            sub_repo.set_head_detached(state.commit)?;
+        info!("Rollback complete. All submodules restored to original state.");
+        Ok(())
+    }
+
+    /// Restore the URL and the original commit of one submodule.
+    /// Submodules that did not exist before are left alone.
+    pub fn restore(&self, repo: &Repository, name: &str) -> Result<()> {
+        let Some(state) = self.original_states.get(name) else {
+            return Ok(());
+        };
 
-            // Update the superproject's index
-            let mut index = repo.index()?;
-            index.add_path(&state.path)?;
-            index.write()?;
+        let key = format!("submodule.{name}.url");
+        Config::open(&gitmodules_path(repo)?)?.set_str(&key, &state.url)?;
+        let mut config = local_config(repo)?;
+        match self.config_entries.iter().find(|(entry, _)| *entry == key) {
+            Some((_, value)) => config.set_str(&key, value)?,
+            None => {
+                let _ = config.remove(&key);
+            }
         }
 
-        info!("Rollback complete. All submodules restored to original state.");
+        restore_checkout(repo, state)
+    }
+
+    /// Undo a removal of one submodule that failed part way: move it
+    /// back from its backup and restore its index entry, its section of
+    /// `.gitmodules` and its configuration.
+    pub fn restore_removal(&self, repo: &Repository, name: &str) -> Result<()> {
+        let Some(state) = self.original_states.get(name) else {
+            return Ok(());
+        };
+        info!("  Restoring the partially removed {}", state.path.display());
+
+        let prefix = format!("submodule.{name}.");
+        let gitmodules_path = gitmodules_path(repo)?;
+        restore_entries(
+            &mut Config::open(&gitmodules_path)?,
+            &prefix,
+            &self.gitmodules_entries,
+        )?;
+        restore_entries(&mut local_config(repo)?, &prefix, &self.config_entries)?;
+        restore_removed(repo, state)?;
+
+        let mut index = repo.index()?;
+        index.read(true)?;
+        let path = state.path.to_string_lossy();
+        if let Some(record) = self.index_entries.iter().find(|record| record.path == path) {
+            index.add(&record.entry())?;
+        }
+        index.add_path(Path::new(".gitmodules"))?;
+        index.write()?;
+        Ok(())
+    }
+
+    /// Delete the removed submodules kept for rollback, once the run
+    /// completed.
+    pub fn discard_backups(repo: &Repository) -> Result<()> {
+        let dir = repo.path().join(BACKUP_DIR);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(())
+    }
+
+    /// Write back the original `.gitmodules`, or remove it if there
+    /// was none.
+    fn restore_gitmodules(&self, repo: &Repository) -> Result<()> {
+        let path = gitmodules_path(repo)?;
+        match &self.gitmodules {
+            Some(content) => fs::write(&path, content)?,
+            None if path.exists() => fs::remove_file(&path)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Replace the `submodule.*` entries of `.git/config` with the
+    /// original ones.
+    fn restore_config(&self, repo: &Repository) -> Result<()> {
+        restore_entries(&mut local_config(repo)?, "submodule.", &self.config_entries)
+    }
+
+    /// Replace the index entries of the submodules and `.gitmodules`
+    /// with the original ones.
+    fn restore_index(&self, repo: &Repository) -> Result<()> {
+        let mut index = repo.index()?;
+        index.read(true)?;
+
+        for record in submodule_index_entries(repo)? {
+            if !self.index_entries.contains(&record) {
+                index.remove_path(Path::new(&record.path))?;
+            }
+        }
+        for record in &self.index_entries {
+            index.add(&record.entry())?;
+        }
+
+        index.write()?;
         Ok(())
     }
 }
 
+/// Move a removed submodule back from its backup. Locations that are
+/// not empty again are left alone. Without a backup of the working
+/// directory, it is checked out again.
+fn restore_removed(repo: &Repository, state: &SubmoduleState) -> Result<()> {
+    let backup = SubmoduleStateTracker::backup_dir(repo, &state.name);
+    if !backup.exists() {
+        return Ok(());
+    }
+    info!("  Restoring removed submodule {}", state.path.display());
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| eyre!("The repository has no working directory"))?;
+    let moves = [
+        (
+            backup.join("modules"),
+            repo.path().join("modules").join(&state.name),
+        ),
+        (backup.join("worktree"), workdir.join(&state.path)),
+    ];
+
+    for (from, to) in moves {
+        let is_empty = fs::read_dir(&to).map_or(true, |mut entries| entries.next().is_none());
+        if !from.exists() || !is_empty {
+            continue;
+        }
+        if to.exists() {
+            fs::remove_dir(&to)?;
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&from, &to)?;
+    }
+
+    if !workdir.join(&state.path).join(".git").exists() {
+        recreate_worktree(repo, &state.name, &state.path)?;
+    }
+    Ok(())
+}
+
+/// Check out the original commit of a submodule and restore its remote.
+fn restore_checkout(repo: &Repository, state: &SubmoduleState) -> Result<()> {
+    info!("  Restoring {} to commit {}", state.name, state.commit);
+
+    let submodule = repo.find_submodule(&state.name)?;
+    let sub_repo = submodule.open()?;
+
+    // Checkout the original commit
+    let obj = sub_repo.find_object(state.commit, None)?;
+    sub_repo.checkout_tree(&obj, None)?;
+    sub_repo.set_head_detached(state.commit)?;
+
+    if let Some(url) = &state.remote_url {
+        sub_repo.remote_set_url("origin", url)?;
+    }
+
+    // Update the superproject's index
+    let mut index = repo.index()?;
+    index.add_path(&state.path)?;
+    index.write()?;
+
+    Ok(())
+}
+
+/// The path of `.gitmodules` in the working directory.
+fn gitmodules_path(repo: &Repository) -> Result<PathBuf> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| eyre!("The repository has no working directory"))?;
+    Ok(workdir.join(".gitmodules"))
+}
+
+/// The configuration file of the repository itself, `.git/config`.
+fn local_config(repo: &Repository) -> Result<Config> {
+    Ok(Config::open(&repo.path().join("config"))?)
+}
+
+/// The `submodule.*` entries of a configuration, in order.
+fn submodule_config_entries(config: &Config) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut iter = config.entries(Some("^submodule\\."))?;
+    while let Some(entry) = iter.next() {
+        let entry = entry?;
+        if let (Some(name), Some(value)) = (entry.name(), entry.value()) {
+            entries.push((name.to_string(), value.to_string()));
+        }
+    }
+    Ok(entries)
+}
+
+/// Replace the `submodule.*` entries of a configuration file whose
+/// keys start with `prefix` with the original ones.
+fn restore_entries(config: &mut Config, prefix: &str, original: &[(String, String)]) -> Result<()> {
+    for (key, _) in submodule_config_entries(config)? {
+        if key.starts_with(prefix) {
+            // Already gone if the key had several values
+            let _ = config.remove_multivar(&key, ".*");
+        }
+    }
+    // Each value is added, so that keys with several values are
+    // restored with all of them
+    for (key, value) in original.iter().filter(|(key, _)| key.starts_with(prefix)) {
+        config.set_multivar(key, MATCH_NO_VALUE, value)?;
+    }
+    Ok(())
+}
+
+/// The index entries of the submodules and `.gitmodules`.
+fn submodule_index_entries(repo: &Repository) -> Result<Vec<IndexRecord>> {
+    let index = repo.index()?;
+    Ok(index
+        .iter()
+        .filter(|entry| entry.mode == GITLINK_MODE || entry.path == b".gitmodules")
+        .map(|entry| IndexRecord {
+            path: String::from_utf8_lossy(&entry.path).into_owned(),
+            mode: entry.mode,
+            id: entry.id,
+        })
+        .collect())
+}
+
+fn serialize_oid<S: Serializer>(oid: &Oid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(oid)
+}
+
+fn deserialize_oid<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Oid, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Oid::from_str(&text).map_err(serde::de::Error::custom)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,8 +425,38 @@ mod tests {
         // For now, we test the basic structure
         let tracker = SubmoduleStateTracker {
             original_states: HashMap::new(),
+            ..Default::default()
         };
 
         assert!(tracker.original_states.is_empty());
     }
+
+    #[test]
+    fn test_restore_config_keeps_multivars() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let entries: Vec<(String, String)> = [
+            ("submodule.a.url", "https://example.com/a.git"),
+            ("submodule.a.extra", "one"),
+            ("submodule.a.extra", ""),
+            ("submodule.a.extra", "one"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let tracker = SubmoduleStateTracker {
+            config_entries: entries.clone(),
+            ..Default::default()
+        };
+
+        let mut config = local_config(&repo).unwrap();
+        config
+            .set_str("submodule.a.url", "https://example.com/b.git")
+            .unwrap();
+        config.set_str("submodule.b.url", "file:///b").unwrap();
+
+        tracker.restore_config(&repo).unwrap();
+        let config = local_config(&repo).unwrap();
+        assert_eq!(submodule_config_entries(&config).unwrap(), entries);
+    }
 }
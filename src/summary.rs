@@ -0,0 +1,125 @@
+use crate::{git_ops::UpdateKind, repo_url::RepoUrl};
+use color_eyre::Report;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// Noteworthy events of an import run, reported when it ends
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    updates: Vec<(PathBuf, UpdateKind)>,
+    stash_conflicts: Vec<(PathBuf, String)>,
+    fallbacks: Vec<(PathBuf, RepoUrl)>,
+    retries: Vec<(PathBuf, usize)>,
+    failures: Vec<Failure>,
+}
+
+/// A repository that failed in keep-going mode
+#[derive(Debug)]
+struct Failure {
+    path: PathBuf,
+    operation: &'static str,
+    error: String,
+}
+
+impl RunSummary {
+    /// Record how an existing submodule was moved.
+    pub fn record_update(&mut self, path: &Path, kind: UpdateKind) {
+        if kind != UpdateKind::Unchanged {
+            self.updates.push((path.to_path_buf(), kind));
+        }
+    }
+
+    /// Record that the stashed local changes of a submodule could not
+    /// be applied again after the update.
+    pub fn record_stash_conflict(&mut self, path: &Path, error: &git2::Error) {
+        self.stash_conflicts
+            .push((path.to_path_buf(), error.message().to_string()));
+    }
+
+    /// Record that a repository was fetched from a fallback URL.
+    pub fn record_fallback(&mut self, path: &Path, url: &RepoUrl) {
+        self.fallbacks.push((path.to_path_buf(), url.clone()));
+    }
+
+    /// Record how often fetching a repository was retried.
+    pub fn record_retries(&mut self, path: &Path, retries: usize) {
+        if retries > 0 {
+            self.retries.push((path.to_path_buf(), retries));
+        }
+    }
+
+    /// Record that an operation on a repository failed.
+    pub fn record_failure(&mut self, path: &Path, operation: &'static str, error: &Report) {
+        self.failures.push(Failure {
+            path: path.to_path_buf(),
+            operation,
+            error: error.to_string(),
+        });
+    }
+
+    /// The number of repositories that failed.
+    pub fn failure_count(&self) -> usize {
+        self.failures.len()
+    }
+
+    /// Log the summary.
+    pub fn report(&self) {
+        if !self.updates.is_empty() {
+            info!("{} submodules were updated:", self.updates.len());
+            for (path, kind) in &self.updates {
+                if kind.is_fast_forward() {
+                    info!("  {}: {kind}", path.display());
+                } else {
+                    warn!("  {}: {kind}", path.display());
+                }
+            }
+        }
+        if !self.stash_conflicts.is_empty() {
+            warn!(
+                "{} submodules have local changes that conflict with the update; \
+                they are kept in their stashes:",
+                self.stash_conflicts.len()
+            );
+            for (path, error) in &self.stash_conflicts {
+                warn!("  {}: {error}", path.display());
+            }
+        }
+        if !self.fallbacks.is_empty() {
+            warn!(
+                "{} repositories were fetched from fallback URLs:",
+                self.fallbacks.len()
+            );
+            for (path, url) in &self.fallbacks {
+                info!("  {}: {url}", path.display());
+            }
+        }
+        if !self.retries.is_empty() {
+            warn!(
+                "{} repositories needed retries after network failures:",
+                self.retries.len()
+            );
+            for (path, retries) in &self.retries {
+                info!("  {}: {retries} retries", path.display());
+            }
+        }
+        if !self.failures.is_empty() {
+            error!("{} repositories failed:", self.failures.len());
+            let width = self
+                .failures
+                .iter()
+                .map(|failure| failure.path.as_os_str().len())
+                .chain(["REPOSITORY".len()])
+                .max()
+                .unwrap_or_default();
+            error!("  {:width$}  {:9}  ERROR", "REPOSITORY", "OPERATION");
+            for failure in &self.failures {
+                error!(
+                    "  {:width$}  {:9}  {}",
+                    failure.path.display(),
+                    failure.operation,
+                    failure.error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use crate::{
+    git_ops::recreate_worktree,
+    state::{SubmoduleState, SubmoduleStateTracker},
+};
+use color_eyre::{
+    eyre::{bail, ensure, eyre, Context},
+    Result,
+};
+use git2::{Config, Repository};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// The trash directory, relative to the Git directory of the
+/// superproject.
+const TRASH_DIR: &str = "vcs2git/trash";
+
+/// The metadata file of an entry.
+const ENTRY_FILE: &str = "entry.yaml";
+
+/// The submodules removed by `--sync-selection`, kept until they are
+/// purged
+///
+/// Each entry is a directory holding the module directory from
+/// `.git/modules`, optionally the working directory, and the metadata
+/// needed to add the submodule again.
+#[derive(Debug)]
+pub struct Trash {
+    dir: PathBuf,
+    /// Entries whose objects were reused by submodules added in this
+    /// run, by submodule name.
+    reused: RefCell<Vec<(String, String)>>,
+}
+
+/// A submodule in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub url: String,
+    /// The commit the submodule was at.
+    pub commit: String,
+    /// When the submodule was removed, in seconds since the Unix epoch.
+    pub removed: u64,
+    /// Whether the working directory was kept.
+    pub worktree: bool,
+}
+
+impl Trash {
+    pub fn new(repo: &Repository) -> Self {
+        Self {
+            dir: repo.path().join(TRASH_DIR),
+            reused: RefCell::new(vec![]),
+        }
+    }
+
+    /// Move the submodules removed by a completed run from their
+    /// backups into the trash, and return the new entries. Only the
+    /// given paths are kept, not the removals that failed.
+    pub fn keep_removed(
+        &self,
+        repo: &Repository,
+        tracker: &SubmoduleStateTracker,
+        removed: &[PathBuf],
+        keep_worktrees: bool,
+    ) -> Result<Vec<String>> {
+        let mut ids = vec![];
+        for state in tracker
+            .states()
+            .filter(|state| removed.iter().any(|path| path == state.path()))
+        {
+            let backup = SubmoduleStateTracker::backup_dir(repo, state.name());
+            if backup.exists() {
+                ids.push(self.put(&backup, state, keep_worktrees)?);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// All entries, oldest first.
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?
+        {
+            let path = dir_entry?.path().join(ENTRY_FILE);
+            if path.exists() {
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let entry = serde_yaml::from_str(&text)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a: &TrashEntry, b| (a.removed, &a.id).cmp(&(b.removed, &b.id)));
+        Ok(entries)
+    }
+
+    /// Find an entry by its ID.
+    pub fn get(&self, id: &str) -> Result<TrashEntry> {
+        self.list()?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| eyre!("There is no submodule {id} in the trash"))
+    }
+
+    /// Move an entry back to where the state tracker restores removed
+    /// submodules from.
+    pub fn take(&self, repo: &Repository, id: &str) -> Result<()> {
+        let entry = self.get(id)?;
+        let backup = SubmoduleStateTracker::backup_dir(repo, &entry.name);
+        fs::create_dir_all(&backup)
+            .with_context(|| format!("Failed to create {}", backup.display()))?;
+        for dir in ["modules", "worktree"] {
+            let from = self.dir.join(id).join(dir);
+            if from.exists() {
+                fs::rename(&from, backup.join(dir))
+                    .with_context(|| format!("Failed to move {}", from.display()))?;
+            }
+        }
+        self.purge(id)
+    }
+
+    /// Add a submodule in the trash to the superproject again, at the
+    /// commit it was removed at.
+    pub fn restore(&self, repo: &Repository, id: &str) -> Result<()> {
+        let entry = self.get(id)?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| eyre!("The repository has no working directory"))?;
+
+        let gitmodules_path = workdir.join(".gitmodules");
+        let in_gitmodules = gitmodules_path.exists()
+            && Config::open(&gitmodules_path)?
+                .get_string(&format!("submodule.{}.path", entry.name))
+                .is_ok();
+        let in_index = repo.index()?.get_path(&entry.path, 0).is_some();
+        ensure!(
+            !in_gitmodules && !in_index,
+            "There is already a submodule at {}",
+            entry.path.display()
+        );
+        let modules_path = repo.path().join("modules").join(&entry.name);
+        ensure!(
+            !modules_path.exists(),
+            "{} already exists",
+            modules_path.display()
+        );
+        let worktree = workdir.join(&entry.path);
+        if fs::read_dir(&worktree).is_ok_and(|mut entries| entries.next().is_some()) {
+            bail!("{} already exists and is not empty", entry.path.display());
+        }
+
+        // The module and working directories
+        if let Some(parent) = modules_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.dir.join(id).join("modules"), &modules_path)
+            .with_context(|| format!("Failed to restore {}", modules_path.display()))?;
+        let kept_worktree = self.dir.join(id).join("worktree");
+        if kept_worktree.exists() {
+            if worktree.exists() {
+                fs::remove_dir(&worktree)?;
+            }
+            if let Some(parent) = worktree.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&kept_worktree, &worktree)
+                .with_context(|| format!("Failed to restore {}", worktree.display()))?;
+        } else {
+            recreate_worktree(repo, &entry.name, &entry.path)?;
+        }
+
+        // .gitmodules, the configuration and the index
+        let path = entry.path.to_string_lossy();
+        let mut gitmodules = Config::open(&gitmodules_path)?;
+        gitmodules.set_str(&format!("submodule.{}.path", entry.name), &path)?;
+        gitmodules.set_str(&format!("submodule.{}.url", entry.name), &entry.url)?;
+        repo.find_submodule(&entry.name)?.init(false)?;
+
+        let mut index = repo.index()?;
+        index.add_path(&entry.path)?;
+        index.add_path(Path::new(".gitmodules"))?;
+        index.write()?;
+
+        self.purge(id)
+    }
+
+    /// Delete an entry.
+    pub fn purge(&self, id: &str) -> Result<()> {
+        let entry = self.get(id)?;
+        let dir = self.dir.join(entry.id);
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))
+    }
+
+    /// Copy the objects and branches of the newest entry of a submodule
+    /// into a new submodule repository with the same URL, so that only
+    /// what is missing has to be fetched.
+    pub fn reuse(&self, name: &str, url: &str, sub_repo: &Repository) -> Result<()> {
+        let Some(entry) = self
+            .list()?
+            .into_iter()
+            .rev()
+            .find(|entry| entry.name == name && entry.url == url)
+        else {
+            return Ok(());
+        };
+
+        info!("Reusing {} from the trash", entry.path.display());
+        let modules_path = self.dir.join(&entry.id).join("modules");
+        let modules_url = modules_path
+            .to_str()
+            .ok_or_else(|| eyre!("Trash path {} is not UTF-8", modules_path.display()))?;
+        let refspecs = [
+            "+refs/heads/*:refs/heads/*",
+            "+refs/remotes/origin/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ];
+        sub_repo
+            .remote_anonymous(modules_url)?
+            .fetch(&refspecs, None, None)?;
+
+        // A retried addition reuses the same entry again
+        let key = (name.to_string(), entry.id.clone());
+        let mut reused = self.reused.borrow_mut();
+        if !reused.contains(&key) {
+            reused.push(key);
+        }
+        Ok(())
+    }
+
+    /// Delete the entries reused by the submodules at the given paths,
+    /// once the run completed. Only submodules that were added
+    /// successfully may be given, since the entries are all that is
+    /// left of the others.
+    pub fn discard_reused(&self, repo: &Repository, added: &[&Path]) -> Result<()> {
+        // The entries are recorded by submodule name, which need not be
+        // the path
+        let names: Vec<String> = added
+            .iter()
+            .filter_map(|path| {
+                let submodule = repo.find_submodule(&path.to_string_lossy()).ok()?;
+                submodule.name().map(str::to_string)
+            })
+            .collect();
+        for (name, id) in self.reused.borrow().iter() {
+            if names.contains(name) {
+                self.purge(id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the backup of a removed submodule into a new entry.
+    fn put(&self, backup: &Path, state: &SubmoduleState, keep_worktree: bool) -> Result<String> {
+        let removed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let base = format!("{removed}-{}", state.name().replace('/', "-"));
+        let id = (1..)
+            .map(|n| match n {
+                1 => base.clone(),
+                n => format!("{base}-{n}"),
+            })
+            .find(|id| !self.dir.join(id).exists())
+            .unwrap();
+
+        let dir = self.dir.join(&id);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let modules = backup.join("modules");
+        if modules.exists() {
+            fs::rename(&modules, dir.join("modules"))
+                .with_context(|| format!("Failed to move {}", modules.display()))?;
+        }
+        let worktree = keep_worktree && backup.join("worktree").exists();
+        if worktree {
+            fs::rename(backup.join("worktree"), dir.join("worktree"))
+                .with_context(|| format!("Failed to move {}", backup.display()))?;
+        }
+        fs::remove_dir_all(backup)
+            .with_context(|| format!("Failed to remove {}", backup.display()))?;
+
+        let entry = TrashEntry {
+            id: id.clone(),
+            name: state.name().to_string(),
+            path: state.path().to_path_buf(),
+            url: state.url().to_string(),
+            commit: state.commit().to_string(),
+            removed,
+            worktree,
+        };
+        let path = dir.join(ENTRY_FILE);
+        fs::write(&path, serde_yaml::to_string(&entry)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(id)
+    }
+}
+
+/// How long ago a Unix timestamp was, for display.
+pub fn format_age(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let secs = now.saturating_sub(timestamp);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} minutes ago", secs / 60),
+        3600..86400 => format!("{} hours ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discard_reused_by_name() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = Repository::init(temp_dir.path())?;
+        fs::write(
+            temp_dir.path().join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = src/lib\n\turl = https://example.com/lib.git\n",
+        )?;
+
+        let trash = Trash::new(&repo);
+        let entry = TrashEntry {
+            id: "1-lib".to_string(),
+            name: "lib".to_string(),
+            path: PathBuf::from("src/lib"),
+            url: "https://example.com/lib.git".to_string(),
+            commit: "0".repeat(40),
+            removed: 1,
+            worktree: false,
+        };
+        let dir = trash.dir.join(&entry.id);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(ENTRY_FILE), serde_yaml::to_string(&entry)?)?;
+        trash
+            .reused
+            .borrow_mut()
+            .push(("lib".to_string(), entry.id.clone()));
+
+        // Another submodule does not discard the entry
+        trash.discard_reused(&repo, &[Path::new("src/other")])?;
+        assert_eq!(trash.list()?.len(), 1);
+
+        trash.discard_reused(&repo, &[Path::new("src/lib")])?;
+        assert!(trash.list()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_age() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(format_age(now), "just now");
+        assert_eq!(format_age(now - 120), "2 minutes ago");
+        assert_eq!(format_age(now - 7200), "2 hours ago");
+        assert_eq!(format_age(now - 3 * 86400), "3 days ago");
+        assert_eq!(format_age(now + 10), "just now");
+    }
+}
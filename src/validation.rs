@@ -1,34 +1,107 @@
-use crate::vcs::Repo;
+use crate::{
+    bundle::BundleDir, git_ops::FETCHED_REFS, mirror::MirrorDir, policy::Policy, repo_url::RepoUrl,
+    vcs::Repo,
+};
 use color_eyre::{
     eyre::{bail, eyre},
     Result,
 };
-use git2::Repository;
+use git2::{Oid, Repository};
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
+    thread,
 };
 
 /// Validate that existing submodules are in a clean state
-pub fn validate_submodule_states(repo: &Repository) -> Result<()> {
+///
+/// Only the submodules at `scope` are checked, or all of them if it is
+/// `None`. Their working directories are scanned concurrently. With
+/// `ignore_untracked`, untracked files do not count as changes.
+/// Changes in the submodules at `handled` are allowed, because the
+/// update stashes or discards them.
+pub fn validate_submodule_states(
+    repo: &Repository,
+    scope: Option<&[&Path]>,
+    ignore_untracked: bool,
+    handled: &[&Path],
+) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| eyre!("The repository has no working directory"))?;
+
+    let mut checks = vec![];
     for submodule in repo.submodules()? {
         let name = submodule
             .name()
             .ok_or_else(|| eyre!("Submodule without name"))?;
         let path = submodule.path();
+        if scope.is_some_and(|scope| !scope.contains(&path)) {
+            continue;
+        }
 
         // Check if submodule is initialized
-        if submodule.workdir_id().is_none() {
+        let Some(expected) = submodule.workdir_id() else {
             bail!(
                 "Submodule '{}' at {} is not initialized. \
                 Please run 'git submodule update --init' first.",
                 name,
                 path.display()
             );
-        }
+        };
+
+        checks.push(SubmoduleCheck {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            dir: workdir.join(path),
+            expected,
+            allow_changes: handled.contains(&path),
+        });
+    }
+
+    // Scan in parallel, and report the first problem in submodule order
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = checks.len().div_ceil(workers).max(1);
+    let results: Vec<Result<()>> = thread::scope(|scope| {
+        let handles: Vec<_> = checks
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|check| check.run(ignore_untracked))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Submodule check panicked"))
+            .collect()
+    });
+    results.into_iter().collect()
+}
+
+/// The state check of one submodule
+struct SubmoduleCheck {
+    name: String,
+    path: PathBuf,
+    /// The working directory of the submodule.
+    dir: PathBuf,
+    /// The commit the superproject expects to be checked out.
+    expected: Oid,
+    /// Whether local changes are allowed.
+    allow_changes: bool,
+}
+
+impl SubmoduleCheck {
+    fn run(&self, ignore_untracked: bool) -> Result<()> {
+        let Self {
+            name, path, dir, ..
+        } = self;
 
         // Open the submodule repository
-        let sub_repo = match submodule.open() {
+        let sub_repo = match Repository::open(dir) {
             Ok(repo) => repo,
             Err(_) => {
                 bail!(
@@ -42,14 +115,14 @@ pub fn validate_submodule_states(repo: &Repository) -> Result<()> {
 
         // Check for uncommitted changes
         let statuses = sub_repo.statuses(None)?;
-        if !statuses.is_empty() {
+        if !self.allow_changes && !statuses.is_empty() {
             let modified_count = statuses
                 .iter()
                 .filter(|s| {
                     let flags = s.status();
                     flags.contains(git2::Status::WT_MODIFIED)
                         || flags.contains(git2::Status::INDEX_MODIFIED)
-                        || flags.contains(git2::Status::WT_NEW)
+                        || (flags.contains(git2::Status::WT_NEW) && !ignore_untracked)
                         || flags.contains(git2::Status::INDEX_NEW)
                 })
                 .count();
@@ -57,7 +130,8 @@ pub fn validate_submodule_states(repo: &Repository) -> Result<()> {
             if modified_count > 0 {
                 bail!(
                     "Submodule '{}' at {} has uncommitted changes. \
-                    Please commit or stash changes before running vcs2git.",
+                    Please commit or stash changes before running vcs2git, \
+                    or update it with --autostash or --force.",
                     name,
                     path.display()
                 );
@@ -69,24 +143,20 @@ pub fn validate_submodule_states(repo: &Repository) -> Result<()> {
             .head()?
             .target()
             .ok_or_else(|| eyre!("Submodule HEAD has no target"))?;
-        let expected_oid = submodule
-            .workdir_id()
-            .ok_or_else(|| eyre!("No workdir commit for submodule"))?;
 
-        if head_oid != expected_oid {
+        if head_oid != self.expected {
             bail!(
                 "Submodule '{}' at {} is checked out to a different commit than expected. \
                 Expected: {}, Actual: {}. \
                 Please run 'git submodule update' to synchronize.",
                 name,
                 path.display(),
-                expected_oid,
+                self.expected,
                 head_oid
             );
         }
+        Ok(())
     }
-
-    Ok(())
 }
 
 /// Validate repositories configuration
@@ -142,6 +212,177 @@ pub fn validate_repositories(
     Ok(())
 }
 
+/// Validate that the repositories to be processed, and the URLs they
+/// are resolved to, comply with the policy
+pub fn validate_policy<'a, I>(policy: &Policy, repos: I) -> Result<()>
+where
+    I: IntoIterator<Item = (&'a Path, &'a Repo, Vec<RepoUrl>)>,
+{
+    let violations = policy.check_resolved(repos);
+
+    if !violations.is_empty() {
+        let report: Vec<String> = violations.iter().map(|v| format!("  {v}")).collect();
+        bail!(
+            "{} repository entries violate the policy:\n{}",
+            violations.len(),
+            report.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Validate that every repository to be processed can be fetched from
+/// its mirror
+pub fn validate_mirror<'a, I>(mirror: &MirrorDir, repos: I) -> Result<()>
+where
+    I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+{
+    let problems = mirror.check(repos);
+
+    if !problems.is_empty() {
+        let report: Vec<String> = problems.iter().map(|p| format!("  {p}")).collect();
+        bail!(
+            "{} repositories cannot be imported offline; run 'vcs2git mirror' to update the mirrors:\n{}",
+            problems.len(),
+            report.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Validate that every repository to be processed has a bundle
+pub fn validate_bundles<'a, I>(bundles: &BundleDir, repos: I) -> Result<()>
+where
+    I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+{
+    let problems = bundles.check(repos);
+
+    if !problems.is_empty() {
+        let report: Vec<String> = problems.iter().map(|p| format!("  {p}")).collect();
+        bail!(
+            "{} repositories cannot be imported from the bundles:\n{}",
+            problems.len(),
+            report.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Validate that removing or updating submodules loses no work that
+/// exists only locally
+///
+/// A removed submodule must have no unpushed commits at HEAD or on
+/// local branches, and no stashes. An updated submodule must have no
+/// unpushed commits at HEAD, which the update would move away from.
+pub fn validate_unpushed_work(
+    repo: &Repository,
+    removed: &[&Path],
+    updated: &[&Path],
+) -> Result<()> {
+    let mut problems = vec![];
+    let candidates = removed
+        .iter()
+        .map(|path| (path, true))
+        .chain(updated.iter().map(|path| (path, false)));
+
+    for (path, removing) in candidates {
+        let submodule = repo.find_submodule(&path.to_string_lossy())?;
+        let Ok(sub_repo) = submodule.open() else {
+            continue;
+        };
+        let work = unpushed_work(&sub_repo, removing)?;
+        if !work.is_empty() {
+            problems.push(format!("  {}: {}", path.display(), work.join(", ")));
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "{} submodules have unpushed work that would be lost:\n{}\n\
+            Push it first, or use --force-remove to remove or update them anyway.",
+            problems.len(),
+            problems.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Describe the local work in a submodule repository: commits at HEAD
+/// and, if `all`, on local branches that are not reachable from a
+/// remote-tracking branch, a tag or the last fetch, and stashes.
+fn unpushed_work(repo: &Repository, all: bool) -> Result<Vec<String>> {
+    let published = published_commits(repo)?;
+    let count = |tip: Oid| -> Result<usize> {
+        let mut walk = repo.revwalk()?;
+        walk.push(tip)?;
+        for id in &published {
+            walk.hide(*id)?;
+        }
+        Ok(walk.count())
+    };
+
+    let mut work = vec![];
+    if let Some(head) = repo.head().ok().and_then(|head| head.target()) {
+        let n = count(head)?;
+        if n > 0 {
+            work.push(format!("{n} unpushed commits at HEAD"));
+        }
+    }
+    if !all {
+        return Ok(work);
+    }
+
+    for branch in repo.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(tip) = branch.get().target() else {
+            continue;
+        };
+        let n = count(tip)?;
+        if n > 0 {
+            let name = branch.name()?.unwrap_or_default().to_string();
+            work.push(format!("branch '{name}' with {n} unpushed commits"));
+        }
+    }
+
+    let stashes = repo.reflog("refs/stash").map_or(0, |reflog| reflog.len());
+    if stashes > 0 {
+        work.push(format!("{stashes} stashes"));
+    }
+
+    Ok(work)
+}
+
+/// The commits that exist outside of a repository: the tips of
+/// remote-tracking branches and tags, and the commits fetched by hash.
+fn published_commits(repo: &Repository) -> Result<Vec<Oid>> {
+    let mut commits = vec![];
+    for reference in repo.references()? {
+        let reference = reference?;
+        let fetched = reference
+            .name()
+            .is_some_and(|name| name.starts_with(FETCHED_REFS));
+        if reference.is_remote() || reference.is_tag() || fetched {
+            if let Ok(commit) = reference.peel_to_commit() {
+                commits.push(commit.id());
+            }
+        }
+    }
+
+    // Fetched by hash before the references were kept
+    let _ = repo.fetchhead_foreach(|_, _, id, _| {
+        if repo.find_commit(*id).is_ok() {
+            commits.push(*id);
+        }
+        true
+    });
+
+    Ok(commits)
+}
+
 /// Validate that the main repository has no staged changes
 pub fn validate_main_repo_clean(repo: &Repository) -> Result<()> {
     let statuses = repo.statuses(None)?;
@@ -179,12 +420,26 @@ mod tests {
                 r#type: RepoType::Git,
                 url: "https://github.com/test/repo1".parse().unwrap(),
                 version: "main".to_string(),
+                fallback_urls: vec![],
             },
         );
 
         // Test with valid repositories
         assert!(validate_repositories(&repos, &PathBuf::from("src")).is_ok());
 
+        // Test with scp-style SSH URL
+        repos.insert(
+            PathBuf::from("src/scp"),
+            Repo {
+                r#type: RepoType::Git,
+                url: "git@github.com:test/scp.git".parse().unwrap(),
+                version: "main".to_string(),
+                fallback_urls: vec![],
+            },
+        );
+        assert!(validate_repositories(&repos, &PathBuf::from("src")).is_ok());
+        repos.shift_remove(&PathBuf::from("src/scp"));
+
         // Test with absolute path
         repos.insert(
             PathBuf::from("/absolute/path"),
@@ -192,6 +447,7 @@ mod tests {
                 r#type: RepoType::Git,
                 url: "https://github.com/test/repo2".parse().unwrap(),
                 version: "main".to_string(),
+                fallback_urls: vec![],
             },
         );
         assert!(validate_repositories(&repos, &PathBuf::from("src")).is_err());
@@ -204,6 +460,7 @@ mod tests {
                 r#type: RepoType::Git,
                 url: "https://github.com/test/repo3".parse().unwrap(),
                 version: "main".to_string(),
+                fallback_urls: vec![],
             },
         );
         assert!(validate_repositories(&repos, &PathBuf::from("src")).is_err());
@@ -216,6 +473,7 @@ mod tests {
                 r#type: RepoType::Git,
                 url: "ftp://github.com/test/repo4".parse().unwrap(),
                 version: "main".to_string(),
+                fallback_urls: vec![],
             },
         );
         assert!(validate_repositories(&repos, &PathBuf::from("src")).is_err());
@@ -240,12 +498,40 @@ mod tests {
         Ok((dir, repo))
     }
 
+    #[test]
+    fn test_unpushed_work() {
+        let (_dir, repo) = create_test_repo().unwrap();
+        let sig = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+
+        // Everything is unpushed without a remote
+        assert_eq!(
+            unpushed_work(&repo, false).unwrap(),
+            vec!["1 unpushed commits at HEAD"]
+        );
+
+        // Published through a remote-tracking branch
+        repo.reference("refs/remotes/origin/main", head.id(), false, "test")
+            .unwrap();
+        assert!(unpushed_work(&repo, true).unwrap().is_empty());
+
+        // A local branch ahead of the remote
+        let tree = head.tree().unwrap();
+        repo.commit(Some("refs/heads/work"), &sig, &sig, "Work", &tree, &[&head])
+            .unwrap();
+        assert!(unpushed_work(&repo, false).unwrap().is_empty());
+        assert_eq!(
+            unpushed_work(&repo, true).unwrap(),
+            vec!["branch 'work' with 1 unpushed commits"]
+        );
+    }
+
     #[test]
     fn test_validate_submodule_states_with_clean_repo() {
         // Create a test repository
         let (_dir, repo) = create_test_repo().unwrap();
 
         // Should pass with no submodules
-        assert!(validate_submodule_states(&repo).is_ok());
+        assert!(validate_submodule_states(&repo, None, false, &[]).is_ok());
     }
 }
@@ -1,18 +1,44 @@
+use crate::repo_url::RepoUrl;
+use color_eyre::{eyre::Context, Result};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use url::Url;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReposFile {
     pub repositories: IndexMap<PathBuf, Repo>,
 }
 
+impl ReposFile {
+    /// Read and parse a repos file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let reader = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+        );
+        serde_yaml::from_reader(reader)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Repo {
     pub r#type: RepoType,
-    pub url: Url,
+    pub url: RepoUrl,
     pub version: String,
+    /// URLs tried in order when fetching from `url` fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_urls: Vec<RepoUrl>,
+}
+
+impl Repo {
+    /// The primary URL followed by the fallback URLs.
+    pub fn urls(&self) -> impl Iterator<Item = &RepoUrl> {
+        std::iter::once(&self.url).chain(&self.fallback_urls)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -22,3 +48,65 @@ pub enum RepoType {
     #[serde(untagged)]
     Unknown(String),
 }
+
+/// The kind of revision a `version` field refers to, guessed from its
+/// spelling alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionKind {
+    /// A full 40-digit commit hash.
+    Commit,
+    /// A commit hash shortened to fewer than 40 digits.
+    AbbreviatedCommit,
+    /// A release-like name such as `1.2.0` or `v0.41.0`.
+    Tag,
+    /// Anything else, assumed to be a branch.
+    Branch,
+}
+
+impl VersionKind {
+    /// Guess the kind of the version without looking at the remote.
+    pub fn of(version: &str) -> Self {
+        let is_hex = !version.is_empty() && version.chars().all(|c| c.is_ascii_hexdigit());
+
+        if is_hex && version.len() == 40 {
+            return Self::Commit;
+        }
+
+        // Short all-numeric names such as "2024" are more likely tags
+        // than abbreviated hashes.
+        if is_hex && version.len() >= 7 && !version.chars().all(|c| c.is_ascii_digit()) {
+            return Self::AbbreviatedCommit;
+        }
+
+        let unprefixed = version.strip_prefix('v').unwrap_or(version);
+        let is_tag = unprefixed.starts_with(|c: char| c.is_ascii_digit())
+            && unprefixed
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+
+        if is_tag {
+            Self::Tag
+        } else {
+            Self::Branch
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_kind() {
+        assert_eq!(
+            VersionKind::of("12287684e096a02d279299a7e6cb44740fc34467"),
+            VersionKind::Commit
+        );
+        assert_eq!(VersionKind::of("1228a68"), VersionKind::AbbreviatedCommit);
+        assert_eq!(VersionKind::of("1.9.0"), VersionKind::Tag);
+        assert_eq!(VersionKind::of("v0.41.0"), VersionKind::Tag);
+        assert_eq!(VersionKind::of("20240101"), VersionKind::Tag);
+        assert_eq!(VersionKind::of("main"), VersionKind::Branch);
+        assert_eq!(VersionKind::of("humble"), VersionKind::Branch);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    repo_url::RepoUrl,
+    vcs::{Repo, VersionKind},
+};
+use color_eyre::Result;
+use git2::{Repository, StatusOptions};
+use std::path::Path;
+
+/// Compare the submodules recorded in the superproject with the
+/// repository entries and describe every mismatch found.
+pub fn verify_submodules<'a, I>(repo: &Repository, entries: I) -> Result<Vec<String>>
+where
+    I: IntoIterator<Item = (&'a Path, &'a Repo)>,
+{
+    let submodules = repo.submodules()?;
+    let mut problems = Vec::new();
+
+    for (path, info) in entries {
+        let Some(submodule) = submodules.iter().find(|submod| submod.path() == path) else {
+            problems.push(format!("{}: not a submodule", path.display()));
+            continue;
+        };
+
+        let recorded_url = submodule.url().and_then(|url| url.parse::<RepoUrl>().ok());
+        let url_matches = recorded_url
+            .as_ref()
+            .is_some_and(|url| url.normalized() == info.url.normalized());
+        if !url_matches {
+            problems.push(format!(
+                "{}: URL is {} but the repos file lists {}",
+                path.display(),
+                submodule.url().unwrap_or("<none>"),
+                info.url
+            ));
+        }
+
+        // Only full hashes can be compared without fetching.
+        if VersionKind::of(&info.version) == VersionKind::Commit {
+            let recorded = submodule.index_id().or_else(|| submodule.head_id());
+            let matches = recorded.is_some_and(|oid| oid.to_string() == info.version);
+            if !matches {
+                problems.push(format!(
+                    "{}: recorded commit is {} but the repos file pins {}",
+                    path.display(),
+                    recorded.map_or_else(|| "<none>".to_string(), |oid| oid.to_string()),
+                    info.version
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Check that every submodule is checked out at the commit recorded in
+/// the superproject and has no local changes, and describe every
+/// mismatch found. With `recursive`, nested submodules are checked too.
+pub fn verify_checkouts(repo: &Repository, recursive: bool) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    for submodule in repo.submodules()? {
+        let path = submodule.path().display().to_string();
+        let (Some(recorded), Some(checked_out)) = (submodule.index_id(), submodule.workdir_id())
+        else {
+            problems.push(format!("{path}: not initialized"));
+            continue;
+        };
+        if checked_out != recorded {
+            problems.push(format!(
+                "{path}: checked out at {checked_out} but the recorded commit is {recorded}"
+            ));
+        }
+
+        // Build artifacts and other untracked files are not changes
+        let sub_repo = submodule.open()?;
+        let mut status_opts = StatusOptions::new();
+        status_opts.include_untracked(false).include_ignored(false);
+        if !sub_repo.statuses(Some(&mut status_opts))?.is_empty() {
+            problems.push(format!("{path}: has local changes"));
+        }
+        if recursive {
+            let nested = verify_checkouts(&sub_repo, true)?;
+            problems.extend(
+                nested
+                    .into_iter()
+                    .map(|problem| format!("{path}/{problem}")),
+            );
+        }
+    }
+
+    Ok(problems)
+}
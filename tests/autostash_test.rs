@@ -0,0 +1,191 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to create a commit on top of the current branch that writes
+/// a file
+fn commit_file(repo: &Repository, name: &str, content: &str) -> Result<Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let workdir = repo.workdir().unwrap();
+    fs::write(workdir.join(name), content)?;
+    let mut index = repo.index()?;
+    index.add_path(Path::new(name))?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    Ok(repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in the main repository with a repos file
+/// pinning the submodule to a version
+fn run_vcs2git(
+    main_repo_path: &Path,
+    sub_path: &Path,
+    version: Oid,
+    args: &[&str],
+) -> Result<Output> {
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/sub:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            sub_path.display()
+        ),
+    )?;
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .arg(&repos_file)
+        .arg("src")
+        .args(args)
+        .output()?)
+}
+
+/// Helper to commit the staged changes of the main repository
+fn commit_all(main_repo_path: &Path) -> Result<()> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = main_repo.find_tree(main_repo.index()?.write_tree()?)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to set up a main repository with a submodule at the first
+/// commit of a repository with two more commits: one changing `b.txt`
+/// and one changing `a.txt`
+fn setup(temp_dir: &TempDir) -> Result<(std::path::PathBuf, std::path::PathBuf, [Oid; 3])> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let sub_path = temp_dir.path().join("sub");
+    let sub_repo = create_test_repo(&sub_path)?;
+    let first = commit_file(&sub_repo, "a.txt", "one\n")?;
+    let second = commit_file(&sub_repo, "b.txt", "two\n")?;
+    let third = commit_file(&sub_repo, "a.txt", "three\n")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, first, &[])?;
+    assert!(output.status.success());
+    commit_all(&main_repo_path)?;
+    Ok((main_repo_path, sub_path, [first, second, third]))
+}
+
+/// Helper to get the commit of the submodule
+fn submodule_commit(main_repo_path: &Path) -> Result<Option<Oid>> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let submodule = main_repo.find_submodule("src/test/sub")?;
+    Ok(submodule.workdir_id())
+}
+
+#[test]
+fn test_autostash_keeps_local_changes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, sub_path, [_, second, _]) = setup(&temp_dir)?;
+    let checkout = main_repo_path.join("src/test/sub");
+    fs::write(checkout.join("a.txt"), "local\n")?;
+    fs::write(checkout.join("notes.txt"), "notes\n")?;
+
+    // Refused without an option
+    let output = run_vcs2git(&main_repo_path, &sub_path, second, &[])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("has uncommitted changes"),
+        "stderr: {stderr}"
+    );
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, second, &["--autostash"])?;
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(submodule_commit(&main_repo_path)?, Some(second));
+    assert_eq!(fs::read_to_string(checkout.join("a.txt"))?, "local\n");
+    assert_eq!(fs::read_to_string(checkout.join("b.txt"))?, "two\n");
+    assert_eq!(fs::read_to_string(checkout.join("notes.txt"))?, "notes\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_autostash_reports_conflicts() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, sub_path, [_, _, third]) = setup(&temp_dir)?;
+    let checkout = main_repo_path.join("src/test/sub");
+    fs::write(checkout.join("a.txt"), "local\n")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, third, &["--autostash"])?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("1 submodules have local changes that conflict with the update"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(submodule_commit(&main_repo_path)?, Some(third));
+
+    // The conflicts are left to resolve, and the changes are still in
+    // the stash
+    assert!(fs::read_to_string(checkout.join("a.txt"))?.contains("<<<<<<<"));
+    let mut sub_repo = Repository::open(&checkout)?;
+    let mut stashes = 0;
+    sub_repo.stash_foreach(|_, _, _| {
+        stashes += 1;
+        true
+    })?;
+    assert_eq!(stashes, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_force_discards_local_changes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, sub_path, [_, _, third]) = setup(&temp_dir)?;
+    let checkout = main_repo_path.join("src/test/sub");
+    fs::write(checkout.join("a.txt"), "local\n")?;
+    fs::write(checkout.join("notes.txt"), "notes\n")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, third, &["--force"])?;
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(submodule_commit(&main_repo_path)?, Some(third));
+    assert_eq!(fs::read_to_string(checkout.join("a.txt"))?, "three\n");
+    assert_eq!(fs::read_to_string(checkout.join("notes.txt"))?, "notes\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_ignore_untracked() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, sub_path, [_, second, _]) = setup(&temp_dir)?;
+    let checkout = main_repo_path.join("src/test/sub");
+    fs::write(checkout.join("notes.txt"), "notes\n")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, second, &[])?;
+    assert!(!output.status.success());
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, second, &["--ignore-untracked"])?;
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(submodule_commit(&main_repo_path)?, Some(second));
+    assert_eq!(fs::read_to_string(checkout.join("notes.txt"))?, "notes\n");
+
+    Ok(())
+}
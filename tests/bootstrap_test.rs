@@ -0,0 +1,172 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to create a commit on top of the current branch that writes
+/// a file
+fn commit_file(repo: &Repository, name: &str, content: &str) -> Result<Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let workdir = repo.workdir().unwrap();
+    fs::write(workdir.join(name), content)?;
+    let mut index = repo.index()?;
+    index.add_path(Path::new(name))?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    Ok(repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in a repository with a repos file listing
+/// repositories at versions
+fn run_vcs2git(repo_path: &Path, repos: &[(&str, &Path, Oid)]) -> Result<Output> {
+    let mut content = "repositories:\n".to_string();
+    for (name, path, version) in repos {
+        content += &format!(
+            "  {name}:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            path.display()
+        );
+    }
+    let repos_file = repo_path.join("test.repos");
+    fs::write(&repos_file, content)?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(repo_path)
+        .arg(&repos_file)
+        .arg("src")
+        .output()?;
+    fs::remove_file(&repos_file)?;
+    Ok(output)
+}
+
+/// Helper to commit the staged changes of a repository
+fn commit_all(repo_path: &Path) -> Result<()> {
+    let repo = Repository::open(repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    repo.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to run the bootstrap command
+fn run_bootstrap(repo_path: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(repo_path)
+        .arg("bootstrap")
+        .args(args)
+        .output()?)
+}
+
+/// Helper to set up a superproject with a submodule that has a nested
+/// submodule, and clone it without initializing them
+fn setup(temp_dir: &TempDir) -> Result<std::path::PathBuf> {
+    let inner_path = temp_dir.path().join("inner");
+    let inner = create_test_repo(&inner_path)?;
+    let inner_commit = commit_file(&inner, "README", "inner")?;
+
+    let outer_path = temp_dir.path().join("outer");
+    let outer = create_test_repo(&outer_path)?;
+    commit_file(&outer, "README", "outer")?;
+    let output = run_vcs2git(&outer_path, &[("inner", &inner_path, inner_commit)])?;
+    assert!(output.status.success(), "{output:?}");
+    commit_all(&outer_path)?;
+    let outer_commit = outer.head()?.target().unwrap();
+
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let output = run_vcs2git(&main_repo_path, &[("outer", &outer_path, outer_commit)])?;
+    assert!(output.status.success(), "{output:?}");
+    commit_all(&main_repo_path)?;
+
+    let clone_path = temp_dir.path().join("clone");
+    Repository::clone(main_repo_path.to_str().unwrap(), &clone_path)?;
+    Ok(clone_path)
+}
+
+#[test]
+fn test_bootstrap_sets_up_nested_submodules() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let clone_path = setup(&temp_dir)?;
+
+    let output = run_bootstrap(&clone_path, &[])?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("All submodules are checked out at their recorded commits"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(
+        fs::read_to_string(clone_path.join("src/outer/README"))?,
+        "outer"
+    );
+    assert_eq!(
+        fs::read_to_string(clone_path.join("src/outer/src/inner/README"))?,
+        "inner"
+    );
+
+    // Nothing is left to do
+    let output = run_bootstrap(&clone_path, &[])?;
+    assert!(output.status.success(), "{output:?}");
+
+    Ok(())
+}
+
+#[test]
+fn test_bootstrap_no_recursive() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let clone_path = setup(&temp_dir)?;
+
+    let output = run_bootstrap(&clone_path, &["--no-recursive"])?;
+    assert!(output.status.success(), "{output:?}");
+    assert!(clone_path.join("src/outer/README").exists());
+    assert!(!clone_path.join("src/outer/src/inner/README").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_bootstrap_ignores_untracked_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let clone_path = setup(&temp_dir)?;
+    let output = run_bootstrap(&clone_path, &[])?;
+    assert!(output.status.success(), "{output:?}");
+
+    // A build artifact is not a local change
+    fs::create_dir(clone_path.join("src/outer/build"))?;
+    fs::write(clone_path.join("src/outer/build/outer.o"), "artifact")?;
+    let output = run_bootstrap(&clone_path, &[])?;
+    assert!(output.status.success(), "{output:?}");
+
+    // A modified file is
+    fs::write(clone_path.join("src/outer/README"), "modified")?;
+    let output = run_bootstrap(&clone_path, &[])?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("src/outer: has local changes"),
+        "stdout: {stdout}"
+    );
+
+    Ok(())
+}
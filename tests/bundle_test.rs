@@ -0,0 +1,263 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+#[test]
+fn test_bundle_create_and_import() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let upstream_path = temp_dir.path().join("upstream");
+    let bundle_dir = temp_dir.path().join("bundles");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir_all(upstream_path.join("a"))?;
+    fs::create_dir_all(upstream_path.join("b"))?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&upstream_path.join("a"))?;
+    let pinned = create_test_repo(&upstream_path.join("b"))?
+        .head()?
+        .target()
+        .unwrap();
+
+    let repos_content = format!(
+        r#"repositories:
+  core/a:
+    type: git
+    url: file://{0}/a
+    version: main
+  core/b:
+    type: git
+    url: file://{0}/b
+    version: {pinned}
+"#,
+        upstream_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            "bundle",
+            "create",
+            repos_file.to_str().unwrap(),
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(bundle_dir.join("manifest.yaml").exists());
+    assert!(bundle_dir.join("0-core-a.bundle").exists());
+    assert!(bundle_dir.join("1-core-b.bundle").exists());
+    assert!(!bundle_dir.join(".vcs2git-work").exists());
+
+    // Import without access to the upstream repositories
+    fs::remove_dir_all(&upstream_path)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--from-bundles",
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(gitmodules.contains(&format!("url = file://{}/a", upstream_path.display())));
+    assert!(!gitmodules.contains("bundle"));
+
+    let submodule = Repository::open(main_repo_path.join("src/core/b"))?;
+    assert_eq!(submodule.head()?.target(), Some(pinned));
+
+    Ok(())
+}
+
+#[test]
+fn test_bundle_names_do_not_collide() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let upstream_path = temp_dir.path().join("upstream");
+    let bundle_dir = temp_dir.path().join("bundles");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let first = create_test_repo(&upstream_path.join("a"))?
+        .head()?
+        .target()
+        .unwrap();
+    let second_repo = create_test_repo(&upstream_path.join("b"))?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let parent = second_repo.head()?.peel_to_commit()?;
+    let second = second_repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        "Second commit",
+        &parent.tree()?,
+        &[&parent],
+    )?;
+
+    // Both paths would be bundled as core-a.bundle by their names alone
+    let repos_content = format!(
+        r#"repositories:
+  core/a:
+    type: git
+    url: file://{0}/a
+    version: {first}
+  core-a:
+    type: git
+    url: file://{0}/b
+    version: {second}
+"#,
+        upstream_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            "bundle",
+            "create",
+            repos_file.to_str().unwrap(),
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    fs::remove_dir_all(&upstream_path)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--from-bundles",
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let submodule = Repository::open(main_repo_path.join("src/core/a"))?;
+    assert_eq!(submodule.head()?.target(), Some(first));
+    let submodule = Repository::open(main_repo_path.join("src/core-a"))?;
+    assert_eq!(submodule.head()?.target(), Some(second));
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_bundle_create_removes_scratch_dir() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let upstream_path = temp_dir.path().join("upstream");
+    let bundle_dir = temp_dir.path().join("bundles");
+    create_test_repo(&upstream_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  core/a:
+    type: git
+    url: file://{}
+    version: main
+  core/b:
+    type: git
+    url: file://{}
+    version: main
+"#,
+        upstream_path.display(),
+        temp_dir.path().join("missing").display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(temp_dir.path())
+        .args([
+            "bundle",
+            "create",
+            repos_file.to_str().unwrap(),
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!bundle_dir.join(".vcs2git-work").exists());
+    assert!(!bundle_dir.join("manifest.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_import_missing_bundle() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let bundle_dir = temp_dir.path().join("bundles");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&bundle_dir)?;
+    create_test_repo(&main_repo_path)?;
+    fs::write(bundle_dir.join("manifest.yaml"), "repositories: {}\n")?;
+
+    let repos_content = r#"repositories:
+  core/a:
+    type: git
+    url: https://github.com/org/a.git
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--from-bundles",
+            bundle_dir.to_str().unwrap(),
+        ])
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no bundle of https://github.com/org/a.git at version 'main'"),
+        "stderr: {stderr}"
+    );
+
+    Ok(())
+}
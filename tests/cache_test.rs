@@ -0,0 +1,137 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to set up a superproject and a repos file listing the same
+/// upstream repository at two paths
+fn setup_shared_upstream(temp_dir: &TempDir) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let main_repo_path = temp_dir.path().join("main");
+    let upstream_path = temp_dir.path().join("upstream");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&upstream_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&upstream_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  first:
+    type: git
+    url: file://{0}
+    version: main
+  second:
+    type: git
+    url: file://{0}/
+    version: main
+"#,
+        upstream_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    Ok((main_repo_path, repos_file))
+}
+
+fn cached_repos(cache_dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(cache_dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".git") {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+#[test]
+fn test_reference_dir_shares_objects() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = setup_shared_upstream(&temp_dir)?;
+    let cache_dir = temp_dir.path().join("cache");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--reference-dir",
+            cache_dir.to_str().unwrap(),
+        ])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Both spellings of the URL share one cached copy
+    assert_eq!(cached_repos(&cache_dir)?.len(), 1);
+
+    for name in ["src/first", "src/second"] {
+        let alternates = main_repo_path
+            .join(".git/modules")
+            .join(name)
+            .join("objects/info/alternates");
+        let content = fs::read_to_string(&alternates)?;
+        assert!(content.contains(cache_dir.file_name().unwrap().to_str().unwrap()));
+        assert!(main_repo_path.join(name).join(".git").exists());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dissociate_copies_objects() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = setup_shared_upstream(&temp_dir)?;
+    let cache_dir = temp_dir.path().join("cache");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--reference-dir",
+            cache_dir.to_str().unwrap(),
+            "--dissociate",
+        ])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(cached_repos(&cache_dir)?.len(), 1);
+    let alternates = main_repo_path.join(".git/modules/src/first/objects/info/alternates");
+    assert!(!alternates.exists());
+
+    // The submodule stays usable without the cache
+    fs::remove_dir_all(&cache_dir)?;
+    let submodule = Repository::open(main_repo_path.join("src/first"))?;
+    assert!(submodule.head()?.peel_to_commit().is_ok());
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to create a commit on top of a parent
+fn commit_on(repo: &Repository, parent: Oid, message: &str) -> Result<Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let parent = repo.find_commit(parent)?;
+    let tree = parent.tree()?;
+    Ok(repo.commit(None, &sig, &sig, message, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in the main repository with a repos file
+/// pinning the submodule to a version
+fn run_vcs2git(
+    main_repo_path: &Path,
+    sub_path: &Path,
+    version: Oid,
+    args: &[&str],
+) -> Result<Output> {
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/sub:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            sub_path.display()
+        ),
+    )?;
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .arg(&repos_file)
+        .arg("src")
+        .args(args)
+        .output()?)
+}
+
+/// Helper to commit the staged changes of the main repository
+fn commit_all(main_repo_path: &Path) -> Result<()> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = main_repo.find_tree(main_repo.index()?.write_tree()?)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to get the commit of the submodule
+fn submodule_commit(main_repo_path: &Path) -> Result<Option<Oid>> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let submodule = main_repo.find_submodule("src/test/sub")?;
+    Ok(submodule.workdir_id())
+}
+
+#[test]
+fn test_ff_only_refuses_downgrade_and_divergent_updates() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    // first <- second, and first <- other
+    let sub_path = temp_dir.path().join("sub");
+    let sub_repo = create_test_repo(&sub_path)?;
+    let first = sub_repo.head()?.target().unwrap();
+    let second = commit_on(&sub_repo, first, "Second")?;
+    let other = commit_on(&sub_repo, first, "Other")?;
+    sub_repo.reference("refs/heads/second", second, false, "test")?;
+    sub_repo.reference("refs/heads/other", other, false, "test")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, first, &[])?;
+    assert!(output.status.success());
+    commit_all(&main_repo_path)?;
+
+    // A fast-forward is allowed
+    let output = run_vcs2git(&main_repo_path, &sub_path, second, &["--ff-only"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("src/test/sub: fast-forward"),
+        "stdout: {stdout}"
+    );
+    commit_all(&main_repo_path)?;
+
+    // A downgrade and a divergent update are not
+    for (version, kind) in [(first, "downgrade"), (other, "divergent")] {
+        let output = run_vcs2git(&main_repo_path, &sub_path, version, &["--ff-only"])?;
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("Refusing the {kind} update")),
+            "stderr: {stderr}"
+        );
+        assert_eq!(submodule_commit(&main_repo_path)?, Some(second));
+    }
+
+    // Without --ff-only, they are done and reported
+    let output = run_vcs2git(&main_repo_path, &sub_path, other, &[])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("src/test/sub: divergent"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(submodule_commit(&main_repo_path)?, Some(other));
+
+    Ok(())
+}
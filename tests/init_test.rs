@@ -0,0 +1,202 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to create a commit on top of the current branch that writes
+/// a file
+fn commit_file(repo: &Repository, name: &str, content: &str) -> Result<Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let workdir = repo.workdir().unwrap();
+    fs::write(workdir.join(name), content)?;
+    let mut index = repo.index()?;
+    index.add_path(Path::new(name))?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    Ok(repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in a repository with a repos file listing
+/// repositories at versions
+fn run_vcs2git(repo_path: &Path, repos: &[(&str, &Path, Oid)], args: &[&str]) -> Result<Output> {
+    let mut content = "repositories:\n".to_string();
+    for (name, path, version) in repos {
+        content += &format!(
+            "  {name}:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            path.display()
+        );
+    }
+    let repos_file = repo_path.join("test.repos");
+    fs::write(&repos_file, content)?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(repo_path)
+        .arg(&repos_file)
+        .arg("src")
+        .args(args)
+        .output()?;
+    fs::remove_file(&repos_file)?;
+    Ok(output)
+}
+
+/// Helper to commit the staged changes of a repository
+fn commit_all(repo_path: &Path) -> Result<()> {
+    let repo = Repository::open(repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    repo.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to set up a superproject with two submodules and clone it
+/// without initializing them
+fn setup(temp_dir: &TempDir) -> Result<(std::path::PathBuf, [(std::path::PathBuf, Oid); 2])> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let mut subs = vec![];
+    for name in ["one", "two"] {
+        let path = temp_dir.path().join(name);
+        let repo = create_test_repo(&path)?;
+        let commit = commit_file(&repo, "README", name)?;
+        subs.push((path, commit));
+    }
+    let repos: Vec<_> = ["one", "two"]
+        .iter()
+        .zip(&subs)
+        .map(|(name, (path, commit))| (*name, path.as_path(), *commit))
+        .collect();
+    let output = run_vcs2git(&main_repo_path, &repos, &[])?;
+    assert!(output.status.success(), "{output:?}");
+    commit_all(&main_repo_path)?;
+
+    let clone_path = temp_dir.path().join("clone");
+    Repository::clone(main_repo_path.to_str().unwrap(), &clone_path)?;
+    Ok((clone_path, subs.try_into().unwrap()))
+}
+
+#[test]
+fn test_init_bootstraps_a_fresh_clone() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (clone_path, [(one, one_commit), (two, two_commit)]) = setup(&temp_dir)?;
+    let repos = [
+        ("one", one.as_path(), one_commit),
+        ("two", two.as_path(), two_commit),
+    ];
+
+    // Refused without --init
+    let output = run_vcs2git(&clone_path, &repos, &[])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not initialized"), "stderr: {stderr}");
+
+    let output = run_vcs2git(&clone_path, &repos, &["--init"])?;
+    assert!(output.status.success(), "{output:?}");
+
+    let clone = Repository::open(&clone_path)?;
+    for (name, commit) in [("one", one_commit), ("two", two_commit)] {
+        let submodule = clone.find_submodule(&format!("src/{name}"))?;
+        assert_eq!(submodule.workdir_id(), Some(commit));
+        assert_eq!(
+            fs::read_to_string(clone_path.join("src").join(name).join("README"))?,
+            name
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_init_cleans_up_failed_submodules() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (clone_path, [(one, one_commit), (two, two_commit)]) = setup(&temp_dir)?;
+    let repos = [
+        ("one", one.as_path(), one_commit),
+        ("two", two.as_path(), two_commit),
+    ];
+
+    // The second repository is gone
+    fs::remove_dir_all(&two)?;
+
+    let output = run_vcs2git(&clone_path, &repos, &["--init"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 submodules could not be initialized"),
+        "stderr: {stderr}"
+    );
+
+    // The first one is initialized, the second one is not
+    let clone = Repository::open(&clone_path)?;
+    assert_eq!(
+        clone.find_submodule("src/one")?.workdir_id(),
+        Some(one_commit)
+    );
+    assert_eq!(clone.find_submodule("src/two")?.workdir_id(), None);
+    assert!(!clone_path.join(".git/modules/src/two").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_init_updates_shared_cache_once() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    // The same repository at two paths
+    let sub_path = temp_dir.path().join("sub");
+    let sub_repo = create_test_repo(&sub_path)?;
+    let commit = commit_file(&sub_repo, "README", "sub")?;
+    let repos = [
+        ("one", sub_path.as_path(), commit),
+        ("two", sub_path.as_path(), commit),
+    ];
+    let output = run_vcs2git(&main_repo_path, &repos, &[])?;
+    assert!(output.status.success(), "{output:?}");
+    commit_all(&main_repo_path)?;
+
+    let clone_path = temp_dir.path().join("clone");
+    Repository::clone(main_repo_path.to_str().unwrap(), &clone_path)?;
+
+    let cache_dir = temp_dir.path().join("cache");
+    let cache_arg = cache_dir.to_str().unwrap();
+    let output = run_vcs2git(
+        &clone_path,
+        &repos,
+        &["--init", "--reference-dir", cache_arg],
+    )?;
+    assert!(output.status.success(), "{output:?}");
+
+    let clone = Repository::open(&clone_path)?;
+    for name in ["one", "two"] {
+        let submodule = clone.find_submodule(&format!("src/{name}"))?;
+        assert_eq!(submodule.workdir_id(), Some(commit));
+    }
+    assert_eq!(fs::read_dir(&cache_dir)?.count(), 1);
+
+    Ok(())
+}
@@ -240,3 +240,80 @@ fn test_unsupported_repository_type() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_url_spelling_preserved() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let sub_repo_path = temp_dir.path().join("sub");
+
+    fs::create_dir(&main_repo_path)?;
+    let _main_repo = create_test_repo(&main_repo_path)?;
+    fs::create_dir(&sub_repo_path)?;
+    let _sub_repo = create_test_repo(&sub_repo_path)?;
+
+    // A plain path without a scheme, as accepted by git itself
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: {}
+    version: main
+"#,
+        sub_repo_path.display()
+    );
+
+    let repos_file = main_repo_path.join("test.repos");
+    create_test_repos_file(&repos_file, &repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+
+    if !output.status.success() {
+        eprintln!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+        panic!("vcs2git failed");
+    }
+
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(
+        gitmodules.contains(&format!("url = {}", sub_repo_path.display())),
+        "Unexpected .gitmodules:\n{gitmodules}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_scp_style_url_accepted() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path().join("repo");
+
+    fs::create_dir(&repo_path)?;
+    let _repo = create_test_repo(&repo_path)?;
+
+    let repos_content = r#"repositories:
+  test/remote:
+    type: git
+    url: git@github.com:org/repo.git
+    version: main
+"#;
+
+    let repos_file = repo_path.join("test.repos");
+    create_test_repos_file(&repos_file, repos_content)?;
+
+    // Parsing and validation must accept the scp-like form
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&repo_path)
+        .args([repos_file.to_str().unwrap(), "src", "--dry-run"])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
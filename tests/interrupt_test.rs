@@ -0,0 +1,220 @@
+#![cfg(unix)]
+
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Start a server that accepts connections but never answers. Returns
+/// the repository URL and whether a connection was accepted.
+fn start_unresponsive_server() -> Result<(String, Arc<AtomicBool>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let connected = Arc::new(AtomicBool::new(false));
+
+    let flag = connected.clone();
+    thread::spawn(move || {
+        let mut connections = Vec::new();
+        for stream in listener.incoming() {
+            connections.extend(stream.ok());
+            flag.store(true, Ordering::SeqCst);
+        }
+    });
+
+    Ok((format!("http://{address}/org/hang.git"), connected))
+}
+
+/// Start an import that adds a local repository and then waits for an
+/// unresponsive server, and wait until it does
+fn start_import(temp_dir: &TempDir, args: &[&str]) -> Result<(PathBuf, Child)> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let local_path = temp_dir.path().join("a");
+    create_test_repo(&local_path)?;
+
+    let repos_file = temp_dir.path().join("test.repos");
+    let (url, connected) = start_unresponsive_server()?;
+    fs::write(
+        &repos_file,
+        format!(
+            r#"repositories:
+  test/a:
+    type: git
+    url: file://{}
+    version: main
+  test/b:
+    type: git
+    url: {}
+    version: main
+"#,
+            local_path.display(),
+            url
+        ),
+    )?;
+
+    let child = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .arg(&repos_file)
+        .arg("src")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // A signal only cancels the fetch while it waits for the server
+    let started = Instant::now();
+    while !connected.load(Ordering::SeqCst) {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "the run did not reach test/b"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    Ok((main_repo_path, child))
+}
+
+/// Send signals to a child process, one right after the other
+fn send_signals(child: &Child, signals: &[&str]) -> Result<()> {
+    let script: Vec<String> = signals
+        .iter()
+        .map(|signal| format!("kill -{signal} {}", child.id()))
+        .collect();
+    let status = Command::new("sh")
+        .args(["-c", &script.join(" && ")])
+        .status()?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+fn test_interrupt_rolls_back() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, child) = start_import(&temp_dir, &[])?;
+
+    send_signals(&child, &["INT"])?;
+    let output = child.wait_with_output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Interrupted"), "stderr: {stderr}");
+    assert!(stderr.contains("rolled back"), "stderr: {stderr}");
+
+    // The interrupted step is not retried and everything is rolled back
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Retrying"), "stdout: {stdout}");
+    let main_repo = Repository::open(&main_repo_path)?;
+    assert!(main_repo.submodules()?.is_empty());
+    assert!(!main_repo_path.join("src/test/a").exists());
+    assert!(!main_repo_path.join(".gitmodules").exists());
+    assert!(!main_repo_path.join(".git/vcs2git/journal.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_second_interrupt_exits_immediately() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, child) = start_import(&temp_dir, &[])?;
+
+    send_signals(&child, &["TERM", "INT"])?;
+    let output = child.wait_with_output()?;
+
+    assert_eq!(output.status.code(), Some(130));
+
+    // The journal is left for `vcs2git abort`
+    assert!(main_repo_path.join(".git/vcs2git/journal.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_interrupt_cancels_retry_delay() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    // Nothing listens on the port, so each attempt fails at once
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            r#"repositories:
+  test/sub:
+    type: git
+    url: http://127.0.0.1:{port}/org/sub.git
+    version: main
+"#
+        ),
+    )?;
+
+    let child = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .arg(&repos_file)
+        .arg("src")
+        .args(["--retry-delay", "60"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let journal_path = main_repo_path.join(".git/vcs2git/journal.yaml");
+    let started = Instant::now();
+    while !fs::read_to_string(&journal_path).is_ok_and(|journal| journal.contains("src/test/sub")) {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "the run did not reach test/sub"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(Duration::from_secs(1));
+
+    let interrupted = Instant::now();
+    send_signals(&child, &["INT"])?;
+    let output = child.wait_with_output()?;
+
+    // The run stops without waiting for the retry
+    assert!(interrupted.elapsed() < Duration::from_secs(10));
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Retrying in 60s"), "stdout: {stdout}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("rolled back"), "stderr: {stderr}");
+    assert!(!main_repo_path.join(".git/vcs2git/journal.yaml").exists());
+
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Start a server that accepts connections but never answers
+fn start_unresponsive_server() -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+
+    thread::spawn(move || {
+        let mut connections = Vec::new();
+        for stream in listener.incoming() {
+            connections.extend(stream.ok());
+        }
+    });
+
+    Ok(format!("http://{address}/org/hang.git"))
+}
+
+/// Write a repos file with a local repository at test/a and the given
+/// URL at test/b
+fn write_repos_file(path: &Path, local: &Path, url: &str) -> Result<()> {
+    let content = format!(
+        r#"repositories:
+  test/a:
+    type: git
+    url: file://{}
+    version: main
+  test/b:
+    type: git
+    url: {url}
+    version: main
+"#,
+        local.display()
+    );
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Run vcs2git in the main repository
+fn run_vcs2git(main_repo_path: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .args(args)
+        .output()?)
+}
+
+/// Start an import that hangs while adding test/b, and kill it
+fn run_interrupted_import(temp_dir: &TempDir) -> Result<(PathBuf, PathBuf)> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let local_path = temp_dir.path().join("a");
+    create_test_repo(&local_path)?;
+
+    let repos_file = temp_dir.path().join("test.repos");
+    write_repos_file(&repos_file, &local_path, &start_unresponsive_server()?)?;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .arg(&repos_file)
+        .arg("src")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Wait until the run reaches test/b
+    let journal_path = main_repo_path.join(".git/vcs2git/journal.yaml");
+    let started = Instant::now();
+    while !fs::read_to_string(&journal_path).is_ok_and(|journal| journal.contains("src/test/b")) {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "the run did not reach test/b"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+    child.kill()?;
+    child.wait()?;
+
+    Ok((main_repo_path, repos_file))
+}
+
+#[test]
+fn test_abort_interrupted_run() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = run_interrupted_import(&temp_dir)?;
+    assert!(main_repo_path.join("src/test/a").exists());
+
+    // Another import is refused until the interrupted one is dealt with
+    let output = run_vcs2git(&main_repo_path, &[repos_file.to_str().unwrap(), "src"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("vcs2git resume"), "stderr: {stderr}");
+
+    let output = run_vcs2git(&main_repo_path, &["abort"])?;
+    assert!(output.status.success(), "{output:?}");
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    assert!(main_repo.submodules()?.is_empty());
+    assert!(!main_repo_path.join("src/test/a").exists());
+    assert!(!main_repo_path.join("src/test/b").exists());
+    assert!(!main_repo_path.join(".gitmodules").exists());
+    assert!(!main_repo_path.join(".git/vcs2git/journal.yaml").exists());
+
+    // Nothing is left to abort
+    let output = run_vcs2git(&main_repo_path, &["abort"])?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_resume_interrupted_run() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = run_interrupted_import(&temp_dir)?;
+
+    // The unresponsive repository becomes available
+    let b_path = temp_dir.path().join("b");
+    create_test_repo(&b_path)?;
+    write_repos_file(
+        &repos_file,
+        &temp_dir.path().join("a"),
+        &format!("file://{}", b_path.display()),
+    )?;
+
+    let output = run_vcs2git(&main_repo_path, &["resume"])?;
+    assert!(output.status.success(), "{output:?}");
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    let b = main_repo.find_submodule("src/test/b")?;
+    assert_eq!(
+        b.url(),
+        Some(format!("file://{}", b_path.display()).as_str())
+    );
+    assert!(b.workdir_id().is_some());
+    assert!(main_repo
+        .find_submodule("src/test/a")?
+        .workdir_id()
+        .is_some());
+    assert!(!main_repo_path.join(".git/vcs2git/journal.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_resume_interrupted_removal() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let local_path = temp_dir.path().join("a");
+    create_test_repo(&local_path)?;
+    let c_path = temp_dir.path().join("c");
+    create_test_repo(&c_path)?;
+
+    let repos_file = temp_dir.path().join("test.repos");
+    let write_repos = |repos: &[(&str, String)]| {
+        let mut content = "repositories:\n".to_string();
+        for (name, url) in repos {
+            content.push_str(&format!(
+                "  test/{name}:\n    type: git\n    url: {url}\n    version: main\n"
+            ));
+        }
+        fs::write(&repos_file, content)
+    };
+    let c_url = format!("file://{}", c_path.display());
+    write_repos(&[
+        ("a", format!("file://{}", local_path.display())),
+        ("c", c_url.clone()),
+    ])?;
+    let output = run_vcs2git(&main_repo_path, &[repos_file.to_str().unwrap(), "src"])?;
+    assert!(output.status.success(), "{output:?}");
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    let tree = main_repo.find_tree(main_repo.index()?.write_tree()?)?;
+    main_repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        "Add submodules",
+        &tree,
+        &[&parent],
+    )?;
+
+    // Drop test/a, and hang while updating test/c before it is removed
+    write_repos(&[("c", start_unresponsive_server()?)])?;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .args([repos_file.to_str().unwrap(), "src", "--sync-selection"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let journal_path = main_repo_path.join(".git/vcs2git/journal.yaml");
+    let started = Instant::now();
+    while !fs::read_to_string(&journal_path).is_ok_and(|journal| journal.contains("operation:")) {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "the run did not reach test/c"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+    child.kill()?;
+    child.wait()?;
+
+    // Turn it into a run killed while removing test/a, after its
+    // module directory was moved away
+    let journal = fs::read_to_string(&journal_path)?;
+    let step = "operation: update\n  path: src/test/c";
+    assert!(journal.contains(step), "journal: {journal}");
+    fs::write(
+        &journal_path,
+        journal.replace(step, "operation: remove\n  path: src/test/a"),
+    )?;
+
+    let name = main_repo
+        .find_submodule("src/test/a")?
+        .name()
+        .unwrap()
+        .to_string();
+    let _ = main_repo.config()?.remove(&format!("submodule.{name}.url"));
+    let mut index = main_repo.index()?;
+    index.remove_path(Path::new("src/test/a"))?;
+    index.write()?;
+    let gitmodules_path = main_repo_path.join(".gitmodules");
+    let gitmodules = fs::read_to_string(&gitmodules_path)?;
+    let mut in_section = false;
+    let kept: Vec<&str> = gitmodules
+        .lines()
+        .filter(|line| {
+            if line.starts_with('[') {
+                in_section = line.contains(&format!("\"{name}\""));
+            }
+            !in_section
+        })
+        .collect();
+    fs::write(&gitmodules_path, kept.join("\n") + "\n")?;
+    let backup = main_repo_path.join(".git/vcs2git/removed").join(&name);
+    fs::create_dir_all(&backup)?;
+    fs::rename(
+        main_repo_path.join(".git/modules").join(&name),
+        backup.join("modules"),
+    )?;
+
+    write_repos(&[("c", c_url)])?;
+    let output = run_vcs2git(&main_repo_path, &["resume"])?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Finishing the removal of src/test/a"),
+        "stdout: {stdout}"
+    );
+
+    // The removal is completed and test/a is kept in the trash
+    let main_repo = Repository::open(&main_repo_path)?;
+    let index = main_repo.index()?;
+    assert!(index.get_path(Path::new("src/test/a"), 0).is_none());
+    assert!(index.get_path(Path::new("src/test/c"), 0).is_some());
+    assert!(!fs::read_to_string(&gitmodules_path)?.contains("src/test/a"));
+    assert!(!main_repo_path.join("src/test/a").exists());
+    assert!(!main_repo_path.join(".git/vcs2git/removed").exists());
+    assert_eq!(
+        fs::read_dir(main_repo_path.join(".git/vcs2git/trash"))?.count(),
+        1
+    );
+    assert!(!journal_path.exists());
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to run vcs2git in the main repository
+fn run_vcs2git(main_repo_path: &Path, repos_file: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .arg(repos_file)
+        .arg("src")
+        .args(["--retries", "0"])
+        .args(args)
+        .output()?)
+}
+
+#[test]
+fn test_keep_going_skips_failed_repository() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo1_path = temp_dir.path().join("repo1");
+    let repo2_path = temp_dir.path().join("repo2");
+    create_test_repo(&repo1_path)?;
+    create_test_repo(&repo2_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/repo1:
+    type: git
+    url: file://{}
+    version: main
+  test/invalid:
+    type: git
+    url: file:///nonexistent/repo
+    version: main
+  test/repo2:
+    type: git
+    url: file://{}
+    version: main
+"#,
+        repo1_path.display(),
+        repo2_path.display()
+    );
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(&repos_file, &repos_content)?;
+
+    let output = run_vcs2git(&main_repo_path, &repos_file, &["--keep-going"])?;
+
+    // The run fails, but only the failing repository was rolled back
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 of 3 operations failed"),
+        "stderr: {stderr}"
+    );
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    assert!(main_repo.find_submodule("src/test/repo1").is_ok());
+    assert!(main_repo.find_submodule("src/test/repo2").is_ok());
+    assert!(main_repo.find_submodule("src/test/invalid").is_err());
+    assert!(!main_repo_path.join("src/test/invalid").exists());
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(!gitmodules.contains("invalid"));
+
+    // The failure table lists the failed repository
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 repositories failed"), "stdout: {stdout}");
+    assert!(
+        stdout
+            .lines()
+            .any(|line| line.contains("src/test/invalid") && line.contains("add")),
+        "stdout: {stdout}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_going_restores_failed_update() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let sub_path = temp_dir.path().join("sub");
+    let sub_repo = create_test_repo(&sub_path)?;
+    let first_commit = sub_repo.head()?.peel_to_commit()?.id();
+    let other_path = temp_dir.path().join("other");
+    create_test_repo(&other_path)?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/sub:\n    type: git\n    url: file://{}\n    version: {first_commit}\n",
+            sub_path.display()
+        ),
+    )?;
+    let output = run_vcs2git(&main_repo_path, &repos_file, &[])?;
+    assert!(output.status.success());
+
+    // Commit the addition
+    let main_repo = Repository::open(&main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = main_repo.index()?.write_tree()?;
+    let tree = main_repo.find_tree(tree_id)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, "Add submodule", &tree, &[&parent])?;
+
+    // Ask for a version that does not exist, next to a new repository
+    fs::write(
+        &repos_file,
+        format!(
+            r#"repositories:
+  test/sub:
+    type: git
+    url: file://{}
+    version: no-such-branch
+  test/other:
+    type: git
+    url: file://{}
+    version: main
+"#,
+            sub_path.display(),
+            other_path.display()
+        ),
+    )?;
+    let output = run_vcs2git(&main_repo_path, &repos_file, &["--keep-going"])?;
+
+    assert!(!output.status.success());
+    let main_repo = Repository::open(&main_repo_path)?;
+    let submodule = main_repo.find_submodule("src/test/sub")?;
+    assert_eq!(submodule.workdir_id(), Some(first_commit));
+    assert!(main_repo.find_submodule("src/test/other").is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_going_restores_failed_removal() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let mut repos_content = String::from("repositories:\n");
+    for name in ["r1", "r2", "r3"] {
+        let path = temp_dir.path().join(name);
+        create_test_repo(&path)?;
+        repos_content.push_str(&format!(
+            "  test/{name}:\n    type: git\n    url: file://{}\n    version: main\n",
+            path.display()
+        ));
+    }
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(&repos_file, &repos_content)?;
+    let output = run_vcs2git(&main_repo_path, &repos_file, &[])?;
+    assert!(output.status.success());
+
+    // Commit the additions
+    let main_repo = Repository::open(&main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = main_repo.index()?.write_tree()?;
+    let tree = main_repo.find_tree(tree_id)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        "Add submodules",
+        &tree,
+        &[&parent],
+    )?;
+
+    // Block the backup of r3, so that its removal fails after it was
+    // taken out of the index and .gitmodules
+    let blocked = main_repo_path.join(".git/vcs2git/removed/src/test/r3");
+    fs::create_dir_all(blocked.parent().unwrap())?;
+    fs::write(&blocked, "")?;
+
+    let selection: String = repos_content.split_inclusive('\n').take(5).collect();
+    fs::write(&repos_file, selection)?;
+    let output = run_vcs2git(
+        &main_repo_path,
+        &repos_file,
+        &["--keep-going", "--sync-selection"],
+    )?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 of 3 operations failed"),
+        "stderr: {stderr}"
+    );
+
+    // r2 is removed and kept in the trash
+    let main_repo = Repository::open(&main_repo_path)?;
+    let index = main_repo.index()?;
+    assert!(index.get_path(Path::new("src/test/r2"), 0).is_none());
+    assert!(!main_repo_path.join("src/test/r2").exists());
+    let trash: Vec<_> = fs::read_dir(main_repo_path.join(".git/vcs2git/trash"))?.collect();
+    assert_eq!(trash.len(), 1);
+
+    // r3 is restored completely
+    assert!(index.get_path(Path::new("src/test/r3"), 0).is_some());
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(gitmodules.contains("src/test/r3"), "{gitmodules}");
+    assert!(main_repo
+        .config()?
+        .get_string("submodule.src/test/r3.url")
+        .is_ok());
+    assert!(main_repo_path.join("src/test/r3/.git").exists());
+    assert!(main_repo_path.join(".git/modules/src/test/r3").exists());
+    assert!(main_repo.find_submodule("src/test/r3")?.open().is_ok());
+    let statuses = main_repo.statuses(None)?;
+    assert!(
+        statuses
+            .iter()
+            .all(|status| status.path() != Some("src/test/r3")),
+        "src/test/r3 is modified"
+    );
+
+    Ok(())
+}
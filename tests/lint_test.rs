@@ -0,0 +1,78 @@
+use color_eyre::Result;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_lint_reports_errors() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(
+        &repos_file,
+        r#"repositories:
+  core/a:
+    type: git
+    url: https://github.com/org/a.git
+    version: 1.0.0
+  core/a/nested:
+    type: git
+    url: https://github.com/org/b.git
+    version: 2.0.0
+"#,
+    )?;
+
+    // Lint runs outside of any git repository
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(temp_dir.path())
+        .args(["lint", repos_file.to_str().unwrap()])
+        .output()?;
+
+    assert!(!output.status.success(), "Nested paths should be an error");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("error[nested-path] core/a/nested"),
+        "Unexpected output: {stdout}"
+    );
+
+    // Demoting the rule lets the file pass
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(temp_dir.path())
+        .args([
+            "lint",
+            repos_file.to_str().unwrap(),
+            "--warn",
+            "nested-path",
+        ])
+        .output()?;
+
+    assert!(output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_lint_warnings_do_not_fail() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(
+        &repos_file,
+        r#"repositories:
+  core/a:
+    type: git
+    url: http://github.com/org/a.git
+    version: main
+"#,
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(temp_dir.path())
+        .args(["lint", repos_file.to_str().unwrap()])
+        .output()?;
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("warning[floating-version]"));
+    assert!(stdout.contains("warning[insecure-url]"));
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to mirror an upstream repository and then make the upstream
+/// unreachable
+fn setup_offline(
+    temp_dir: &TempDir,
+    version: &str,
+) -> Result<(std::path::PathBuf, std::path::PathBuf, std::path::PathBuf)> {
+    let main_repo_path = temp_dir.path().join("main");
+    let upstream_path = temp_dir.path().join("upstream/org/sub");
+    let mirror_dir = temp_dir.path().join("mirrors");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir_all(&upstream_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&upstream_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: file://{}
+    version: {version}
+"#,
+        upstream_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            "mirror",
+            repos_file.to_str().unwrap(),
+            mirror_dir.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    fs::remove_dir_all(temp_dir.path().join("upstream"))?;
+
+    Ok((main_repo_path, repos_file, mirror_dir))
+}
+
+#[test]
+fn test_offline_import_from_mirror() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file, mirror_dir) = setup_offline(&temp_dir, "main")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--offline",
+            "--mirror-dir",
+            mirror_dir.to_str().unwrap(),
+        ])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The upstream URL is recorded, not the mirror
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(gitmodules.contains("upstream/org/sub"));
+    assert!(!gitmodules.contains("mirrors"));
+    assert!(main_repo_path.join("src/test/sub/.git").exists());
+
+    // The submodule fetches from upstream again once it is reachable
+    let sub_repo = Repository::open(main_repo_path.join("src/test/sub"))?;
+    let origin = sub_repo.find_remote("origin")?;
+    let origin_url = origin.url().unwrap_or_default();
+    assert!(
+        origin_url.contains("upstream/org/sub") && !origin_url.contains("mirrors"),
+        "origin: {origin_url}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_offline_missing_version() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file, mirror_dir) = setup_offline(&temp_dir, "release")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--offline",
+            "--mirror-dir",
+            mirror_dir.to_str().unwrap(),
+        ])
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("version 'release' is missing from the mirror"),
+        "stderr: {stderr}"
+    );
+    assert!(!main_repo_path.join(".gitmodules").exists());
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+#[test]
+fn test_policy_blocks_import_before_clone() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let sub_repo_path = temp_dir.path().join("sub");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&sub_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&sub_repo_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: file://{}
+    version: main
+"#,
+        sub_repo_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    // The default policy file is picked up from the superproject
+    fs::write(
+        main_repo_path.join(".vcs2git-policy.toml"),
+        "allowed_schemes = [\"https\"]\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+
+    assert!(!output.status.success(), "Import should violate the policy");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("src/test/sub: violates 'allowed_schemes'"),
+        "Unexpected error: {stderr}"
+    );
+    assert!(!main_repo_path.join(".gitmodules").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_enforces_policy() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let sub_repo_path = temp_dir.path().join("sub");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&sub_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&sub_repo_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: file://{}
+    version: main
+"#,
+        sub_repo_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+    assert!(output.status.success());
+
+    // Without a policy the workspace matches the repos file
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args(["verify", repos_file.to_str().unwrap(), "src"])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // A policy requiring pinned commits rejects the branch version
+    let policy_file = temp_dir.path().join("policy.toml");
+    fs::write(&policy_file, "pinning = \"sha\"\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            "verify",
+            repos_file.to_str().unwrap(),
+            "src",
+            "--policy",
+            policy_file.to_str().unwrap(),
+        ])
+        .output()?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("src/test/sub: violates 'pinning'"),
+        "Unexpected output: {stdout}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_policy_checks_rewritten_urls() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    let sub_repo_path = temp_dir.path().join("sub");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&sub_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&sub_repo_path)?;
+
+    let repos_content = r#"repositories:
+  test/sub:
+    type: git
+    url: https://upstream.invalid/sub.git
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let policy_file = temp_dir.path().join("policy.toml");
+    fs::write(&policy_file, "allowed_schemes = [\"https\"]\n")?;
+
+    // The entry complies, but the URL it is fetched from does not
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--policy",
+            policy_file.to_str().unwrap(),
+            "--rewrite-url",
+            &format!("https://upstream.invalid/={}/", temp_dir.path().display()),
+        ])
+        .output()?;
+
+    assert!(!output.status.success(), "Import should violate the policy");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("src/test/sub: violates 'allowed_schemes'"),
+        "Unexpected error: {stderr}"
+    );
+    assert!(!main_repo_path.join(".gitmodules").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_policy_rejects_malformed_forbidden_repositories() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, "repositories: {}\n")?;
+    fs::write(
+        main_repo_path.join(".vcs2git-policy.toml"),
+        "forbidden_repositories = [\"https://\"]\n",
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to parse policy file"),
+        "Unexpected error: {stderr}"
+    );
+
+    Ok(())
+}
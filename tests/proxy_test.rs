@@ -0,0 +1,140 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Start a stand-in proxy that reports the request line of every
+/// request it receives and answers with an error
+fn start_proxy() -> Result<(String, Receiver<String>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            // Skip the headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                line.clear();
+            }
+            let _ = sender.send(request_line.trim().to_string());
+            let _ = stream.write_all(
+                b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+        }
+    });
+
+    Ok((url, receiver))
+}
+
+/// Helper to run an import of a single repository on an unreachable host
+fn run_import(temp_dir: &TempDir, args: &[&str]) -> Result<Output> {
+    let main_repo_path = temp_dir.path().join("main");
+    if !main_repo_path.exists() {
+        fs::create_dir(&main_repo_path)?;
+        create_test_repo(&main_repo_path)?;
+    }
+
+    let repos_content = r#"repositories:
+  test/sub:
+    type: git
+    url: https://git.example.invalid/org/sub.git
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("https_proxy")
+        .env_remove("HTTPS_PROXY")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .env_remove("no_proxy")
+        .env_remove("NO_PROXY")
+        .arg(repos_file.to_str().unwrap())
+        .arg("src")
+        .args(args)
+        .output()?;
+    Ok(output)
+}
+
+#[test]
+fn test_fetch_through_proxy() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (proxy, requests) = start_proxy()?;
+
+    let output = run_import(&temp_dir, &["--proxy", &proxy])?;
+
+    // The stand-in proxy cannot reach the host, so the import fails
+    assert!(!output.status.success());
+    let request = requests.try_recv()?;
+    assert!(
+        request.starts_with("CONNECT git.example.invalid:443"),
+        "request: {request}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_proxy_from_git_config() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (proxy, requests) = start_proxy()?;
+
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    let repo = create_test_repo(&main_repo_path)?;
+    repo.config()?.set_str("http.proxy", &proxy)?;
+
+    let output = run_import(&temp_dir, &[])?;
+
+    assert!(!output.status.success());
+    assert!(requests.try_recv().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_no_proxy_excludes_host() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (proxy, requests) = start_proxy()?;
+
+    let output = run_import(&temp_dir, &["--proxy", &proxy, "--no-proxy", ".invalid"])?;
+
+    assert!(!output.status.success());
+    assert!(requests.try_recv().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Start an HTTP server that answers every request with the given
+/// status. Returns the repository URL and the number of requests made.
+fn start_failing_server(status: &'static str) -> Result<(String, Arc<AtomicUsize>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let counter = requests.clone();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                line.clear();
+            }
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+        }
+    });
+
+    Ok((format!("http://{address}/org/sub.git"), requests))
+}
+
+/// Helper to import a repository from a failing server
+fn run_import(temp_dir: &TempDir, url: &str, args: &[&str]) -> Result<Output> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: {url}
+    version: main
+"#
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .arg(repos_file.to_str().unwrap())
+        .arg("src")
+        .args(args)
+        .output()?;
+    Ok(output)
+}
+
+#[test]
+fn test_server_errors_are_retried() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (url, requests) = start_failing_server("503 Service Unavailable")?;
+
+    let output = run_import(&temp_dir, &url, &["--retries", "2", "--retry-delay", "0"])?;
+
+    assert!(!output.status.success());
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("retry 2 of 2"), "stdout: {stdout}");
+    assert!(stdout.contains("test/sub: 2 retries"), "stdout: {stdout}");
+
+    // The half-created submodule was removed between attempts and
+    // rolled back in the end
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("rolled back"), "stderr: {stderr}");
+    assert!(!temp_dir.path().join("main/src/test/sub").exists());
+    assert!(!temp_dir.path().join("main/.git/modules/test/sub").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_repository_is_not_retried() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (url, requests) = start_failing_server("404 Not Found")?;
+
+    let output = run_import(&temp_dir, &url, &["--retries", "2", "--retry-delay", "0"])?;
+
+    assert!(!output.status.success());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Retrying"), "stdout: {stdout}");
+
+    Ok(())
+}
@@ -336,3 +336,158 @@ fn test_dry_run_rollback_not_triggered() -> Result<()> {
 
     Ok(())
 }
+
+/// Helper to commit the staged changes of the main repository
+fn commit_all(main_repo_path: &Path, message: &str) -> Result<()> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = main_repo.index()?.write_tree()?;
+    let tree = main_repo.find_tree(tree_id)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to read the `submodule.*` entries of `.git/config`
+fn submodule_config(main_repo_path: &Path) -> Result<Vec<(String, String)>> {
+    let config = git2::Config::open(&main_repo_path.join(".git/config"))?;
+    let mut entries = Vec::new();
+    let mut iter = config.entries(Some("^submodule\\."))?;
+    while let Some(entry) = iter.next() {
+        let entry = entry?;
+        entries.push((
+            entry.name().unwrap().to_string(),
+            entry.value().unwrap().to_string(),
+        ));
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+#[test]
+fn test_rollback_restores_changed_url() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo1_path = temp_dir.path().join("repo1");
+    create_test_repo(&repo1_path)?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/repo1:\n    type: git\n    url: file://{}\n    version: main\n",
+            repo1_path.display()
+        ),
+    )?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+    assert!(output.status.success());
+    commit_all(&main_repo_path, "Add submodule")?;
+
+    let gitmodules = fs::read(main_repo_path.join(".gitmodules"))?;
+    let config = submodule_config(&main_repo_path)?;
+
+    // Move the repository to a URL that does not exist
+    fs::write(
+        &repos_file,
+        "repositories:\n  test/repo1:\n    type: git\n    url: file:///nonexistent/repo\n    version: main\n",
+    )?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src", "--retries", "0"])
+        .output()?;
+    assert!(!output.status.success());
+
+    // The URL is restored everywhere
+    assert_eq!(fs::read(main_repo_path.join(".gitmodules"))?, gitmodules);
+    assert_eq!(submodule_config(&main_repo_path)?, config);
+    let sub_repo = Repository::open(main_repo_path.join("src/test/repo1"))?;
+    assert_eq!(
+        sub_repo.find_remote("origin")?.url(),
+        Some(format!("file://{}", repo1_path.display()).as_str())
+    );
+    let main_repo = Repository::open(&main_repo_path)?;
+    let statuses = main_repo.statuses(None)?;
+    assert!(
+        statuses
+            .iter()
+            .all(|entry| entry.path() == Some("test.repos")),
+        "unexpected changes after rollback"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rollback_restores_removed_submodules() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo_paths: Vec<_> = ["repo1", "repo2", "repo3"]
+        .iter()
+        .map(|name| temp_dir.path().join(name))
+        .collect();
+    for path in &repo_paths {
+        create_test_repo_with_content(path, "README.md", "content")?;
+    }
+
+    let repos_file = main_repo_path.join("test.repos");
+    let mut repos_content = "repositories:\n".to_string();
+    for (name, path) in ["repo1", "repo2", "repo3"].iter().zip(&repo_paths) {
+        repos_content.push_str(&format!(
+            "  test/{name}:\n    type: git\n    url: file://{}\n    version: main\n",
+            path.display()
+        ));
+    }
+    fs::write(&repos_file, &repos_content)?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+    assert!(output.status.success());
+    commit_all(&main_repo_path, "Add submodules")?;
+
+    let gitmodules = fs::read(main_repo_path.join(".gitmodules"))?;
+    let config = submodule_config(&main_repo_path)?;
+
+    // Keep only repo1. repo2 is removed, then the removal of repo3
+    // fails because its backup location is already taken.
+    let blocker = main_repo_path.join(".git/vcs2git/removed/src/test/repo3/modules/blocker");
+    fs::create_dir_all(&blocker)?;
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/repo1:\n    type: git\n    url: file://{}\n    version: main\n",
+            repo_paths[0].display()
+        ),
+    )?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src", "--sync-selection"])
+        .output()?;
+    assert!(!output.status.success());
+
+    // Both submodules are back, with their files, configuration and
+    // index entries
+    assert_eq!(fs::read(main_repo_path.join(".gitmodules"))?, gitmodules);
+    assert_eq!(submodule_config(&main_repo_path)?, config);
+    let main_repo = Repository::open(&main_repo_path)?;
+    for name in ["repo2", "repo3"] {
+        let path = format!("src/test/{name}");
+        assert!(main_repo_path.join(&path).join("README.md").exists());
+        assert!(main_repo_path.join(".git/modules").join(&path).exists());
+        let entry = main_repo.index()?.get_path(Path::new(&path), 0);
+        assert_eq!(entry.map(|entry| entry.mode), Some(0o160000));
+        assert!(main_repo.find_submodule(&path)?.open().is_ok());
+    }
+    assert!(!main_repo_path.join(".git/vcs2git/removed").exists());
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to run vcs2git in the main repository with a repos file
+/// listing one repository under a prefix
+fn run_vcs2git(
+    main_repo_path: &Path,
+    sub_path: &Path,
+    version: Oid,
+    prefix: &str,
+    args: &[&str],
+) -> Result<Output> {
+    let repos_file = main_repo_path.join("test.repos");
+    fs::write(
+        &repos_file,
+        format!(
+            "repositories:\n  test/sub:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            sub_path.display()
+        ),
+    )?;
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .arg(&repos_file)
+        .arg(prefix)
+        .args(args)
+        .output()?;
+    fs::remove_file(&repos_file)?;
+    Ok(output)
+}
+
+/// Helper to commit the staged changes of the main repository
+fn commit_all(main_repo_path: &Path) -> Result<()> {
+    let main_repo = Repository::open(main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = main_repo.find_tree(main_repo.index()?.write_tree()?)?;
+    let parent = main_repo.head()?.peel_to_commit()?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, "Update", &tree, &[&parent])?;
+    Ok(())
+}
+
+#[test]
+fn test_validation_skips_submodules_outside_the_plan() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    let sub_path = temp_dir.path().join("sub");
+    let version = create_test_repo(&sub_path)?.head()?.target().unwrap();
+
+    // One submodule under src and one under other
+    for prefix in ["src", "other"] {
+        let output = run_vcs2git(&main_repo_path, &sub_path, version, prefix, &[])?;
+        assert!(output.status.success(), "{output:?}");
+        commit_all(&main_repo_path)?;
+    }
+
+    // The one under other has local changes
+    fs::write(main_repo_path.join("other/test/sub/notes.txt"), "notes\n")?;
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, version, "src", &[])?;
+    assert!(output.status.success(), "{output:?}");
+
+    let output = run_vcs2git(&main_repo_path, &sub_path, version, "src", &["--strict"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Submodule 'other/test/sub' at other/test/sub has uncommitted changes"),
+        "stderr: {stderr}"
+    );
+
+    // It is checked when it is updated
+    let output = run_vcs2git(&main_repo_path, &sub_path, version, "other", &[])?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
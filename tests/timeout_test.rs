@@ -0,0 +1,107 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Start a server that accepts connections but never answers
+fn start_unresponsive_server() -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+
+    thread::spawn(move || {
+        let mut connections = Vec::new();
+        for stream in listener.incoming() {
+            connections.extend(stream.ok());
+        }
+    });
+
+    Ok(format!("http://{address}/org/sub.git"))
+}
+
+/// Helper to import a repository from an unresponsive server
+fn run_import(temp_dir: &TempDir, args: &[&str]) -> Result<(Output, Duration)> {
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: {}
+    version: main
+"#,
+        start_unresponsive_server()?
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .env_remove("http_proxy")
+        .env_remove("all_proxy")
+        .env_remove("ALL_PROXY")
+        .arg(repos_file.to_str().unwrap())
+        .arg("src")
+        .args(args)
+        .output()?;
+    Ok((output, started.elapsed()))
+}
+
+#[test]
+fn test_fetch_timeout() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    let (output, elapsed) = run_import(&temp_dir, &["--timeout", "1"])?;
+
+    assert!(!output.status.success());
+    assert!(elapsed < Duration::from_secs(30), "took {elapsed:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Timed out after 1s"), "stderr: {stderr}");
+    assert!(stderr.contains("rolled back"), "stderr: {stderr}");
+
+    // The failed submodule was rolled back
+    assert!(!temp_dir.path().join("main/.gitmodules").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_host_timeout_overrides_timeout() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    let (output, _) = run_import(
+        &temp_dir,
+        &["--timeout", "600", "--host-timeout", "127.0.0.1=1"],
+    )?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Timed out after 1s"), "stderr: {stderr}");
+
+    Ok(())
+}
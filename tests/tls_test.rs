@@ -0,0 +1,96 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to set up a superproject and a repos file with one local
+/// repository
+fn setup(temp_dir: &TempDir) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let main_repo_path = temp_dir.path().join("main");
+    let sub_repo_path = temp_dir.path().join("sub");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir(&sub_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&sub_repo_path)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: file://{}
+    version: main
+"#,
+        sub_repo_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    Ok((main_repo_path, repos_file))
+}
+
+#[test]
+fn test_insecure_is_logged() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = setup(&temp_dir)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src", "--insecure"])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("NOT verified"), "stdout: {stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_ca_bundle() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repos_file) = setup(&temp_dir)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--ca-bundle",
+            "/nonexistent/ca.pem",
+        ])
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("CA bundle /nonexistent/ca.pem does not exist"),
+        "stderr: {stderr}"
+    );
+    assert!(!main_repo_path.join(".gitmodules").exists());
+
+    Ok(())
+}
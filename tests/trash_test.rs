@@ -0,0 +1,237 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create a file
+    fs::write(path.join("README.md"), "content")?;
+
+    // Add and commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let mut index = repo.index()?;
+    index.add_path(Path::new("README.md"))?;
+    let tree_id = index.write_tree()?;
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to commit the index on top of HEAD to a reference
+fn commit_to(repo: &Repository, reference: &str, message: &str) -> Result<()> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let parent = repo.head()?.peel_to_commit()?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    repo.commit(Some(reference), &sig, &sig, message, &tree, &[&parent])?;
+    Ok(())
+}
+
+/// Helper to run vcs2git in the main repository
+fn run_vcs2git(main_repo_path: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .args(args)
+        .output()?)
+}
+
+/// Helper to write a repos file listing the given repositories
+fn write_repos_file(path: &Path, repos: &[(&str, &Path)]) -> Result<()> {
+    let mut content = "repositories:\n".to_string();
+    for (name, url) in repos {
+        content.push_str(&format!(
+            "  test/{name}:\n    type: git\n    url: file://{}\n    version: main\n",
+            url.display()
+        ));
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Helper to list the IDs of the trash entries
+fn trash_ids(main_repo_path: &Path) -> Result<Vec<String>> {
+    let trash_dir = main_repo_path.join(".git/vcs2git/trash");
+    if !trash_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut ids = vec![];
+    for entry in fs::read_dir(trash_dir)? {
+        ids.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(ids)
+}
+
+/// Add repo1 and repo2, commit, make an unpushed commit in repo2 and
+/// remove it again with --sync-selection --force-remove
+fn remove_with_unpushed_work(temp_dir: &Path) -> Result<(PathBuf, PathBuf, PathBuf)> {
+    let main_repo_path = temp_dir.join("main");
+    fs::create_dir(&main_repo_path)?;
+    let main_repo = Repository::init(&main_repo_path)?;
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree = main_repo.find_tree(main_repo.index()?.write_tree()?)?;
+    main_repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    let repo1_path = temp_dir.join("repo1");
+    let repo2_path = temp_dir.join("repo2");
+    create_test_repo(&repo1_path)?;
+    create_test_repo(&repo2_path)?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    let repos_arg = repos_file.to_str().unwrap();
+    write_repos_file(
+        &repos_file,
+        &[("repo1", &repo1_path), ("repo2", &repo2_path)],
+    )?;
+    assert!(run_vcs2git(&main_repo_path, &[repos_arg, "src"])?
+        .status
+        .success());
+    let main_repo = Repository::open(&main_repo_path)?;
+    commit_to(&main_repo, "HEAD", "Add submodules")?;
+
+    let sub_repo = Repository::open(main_repo_path.join("src/test/repo2"))?;
+    commit_to(&sub_repo, "refs/heads/work", "Unpushed work")?;
+
+    write_repos_file(&repos_file, &[("repo1", &repo1_path)])?;
+    let output = run_vcs2git(
+        &main_repo_path,
+        &[repos_arg, "src", "--sync-selection", "--force-remove"],
+    )?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!main_repo_path.join("src/test/repo2").exists());
+    assert!(!main_repo_path.join(".git/modules/src/test/repo2").exists());
+
+    Ok((main_repo_path, repo1_path, repo2_path))
+}
+
+#[test]
+fn test_trash_list_and_restore() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, _, _) = remove_with_unpushed_work(temp_dir.path())?;
+
+    let output = run_vcs2git(&main_repo_path, &["trash", "list"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("src/test/repo2"), "stdout: {stdout}");
+
+    let ids = trash_ids(&main_repo_path)?;
+    assert_eq!(ids.len(), 1);
+    let output = run_vcs2git(&main_repo_path, &["trash", "restore", &ids[0]])?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    let submodule = main_repo.find_submodule("src/test/repo2")?;
+    let sub_repo = submodule.open()?;
+    assert!(sub_repo
+        .find_branch("work", git2::BranchType::Local)
+        .is_ok());
+    assert!(main_repo_path.join("src/test/repo2/README.md").exists());
+    assert!(main_repo
+        .index()?
+        .get_path(Path::new("src/test/repo2"), 0)
+        .is_some());
+    assert!(trash_ids(&main_repo_path)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_readded_submodule_reuses_trash() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repo1_path, repo2_path) = remove_with_unpushed_work(temp_dir.path())?;
+    let main_repo = Repository::open(&main_repo_path)?;
+    commit_to(&main_repo, "HEAD", "Remove submodule")?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    write_repos_file(
+        &repos_file,
+        &[("repo1", &repo1_path), ("repo2", &repo2_path)],
+    )?;
+    let output = run_vcs2git(&main_repo_path, &[repos_file.to_str().unwrap(), "src"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Reusing src/test/repo2 from the trash"),
+        "stdout: {stdout}"
+    );
+
+    let sub_repo = Repository::open(main_repo_path.join("src/test/repo2"))?;
+    assert!(sub_repo
+        .find_branch("work", git2::BranchType::Local)
+        .is_ok());
+    assert!(trash_ids(&main_repo_path)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_readd_keeps_trash() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, repo1_path, repo2_path) = remove_with_unpushed_work(temp_dir.path())?;
+    let main_repo = Repository::open(&main_repo_path)?;
+    commit_to(&main_repo, "HEAD", "Remove submodule")?;
+
+    // repo2 is added again at a version that does not exist
+    let repos_file = main_repo_path.join("test.repos");
+    write_repos_file(
+        &repos_file,
+        &[("repo1", &repo1_path), ("repo2", &repo2_path)],
+    )?;
+    let content = fs::read_to_string(&repos_file)?;
+    let (repo1, repo2) = content.split_at(content.find("  test/repo2").unwrap());
+    fs::write(
+        &repos_file,
+        format!(
+            "{repo1}{}",
+            repo2.replace("version: main", "version: missing")
+        ),
+    )?;
+
+    let output = run_vcs2git(
+        &main_repo_path,
+        &[repos_file.to_str().unwrap(), "src", "--keep-going"],
+    )?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Reusing src/test/repo2 from the trash"),
+        "stdout: {stdout}"
+    );
+    assert!(!main_repo_path.join("src/test/repo2").exists());
+    assert_eq!(trash_ids(&main_repo_path)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_trash_purge() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, _, _) = remove_with_unpushed_work(temp_dir.path())?;
+
+    // Either IDs or --all is required
+    let output = run_vcs2git(&main_repo_path, &["trash", "purge"])?;
+    assert!(!output.status.success());
+
+    let output = run_vcs2git(&main_repo_path, &["trash", "purge", "no-such-entry"])?;
+    assert!(!output.status.success());
+
+    let output = run_vcs2git(&main_repo_path, &["trash", "purge", "--all"])?;
+    assert!(output.status.success());
+    assert!(trash_ids(&main_repo_path)?.is_empty());
+
+    Ok(())
+}
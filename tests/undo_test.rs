@@ -0,0 +1,168 @@
+use color_eyre::Result;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to add an empty commit on top of HEAD
+fn commit(repo: &Repository, message: &str) -> Result<Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = repo.index()?.write_tree()?;
+    let tree = repo.find_tree(tree_id)?;
+    let parent = repo.head()?.peel_to_commit()?;
+    Ok(repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in the main repository
+fn run_vcs2git(main_repo_path: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .args(args)
+        .output()?)
+}
+
+/// Helper to write a repos file listing the given repositories
+fn write_repos_file(path: &Path, repos: &[(&str, &Path)]) -> Result<()> {
+    let mut content = "repositories:\n".to_string();
+    for (name, url) in repos {
+        content.push_str(&format!(
+            "  test/{name}:\n    type: git\n    url: file://{}\n    version: main\n",
+            url.display()
+        ));
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[test]
+fn test_undo_restores_previous_state() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo1_path = temp_dir.path().join("repo1");
+    let repo2_path = temp_dir.path().join("repo2");
+    let repo3_path = temp_dir.path().join("repo3");
+    let repo1 = create_test_repo(&repo1_path)?;
+    create_test_repo(&repo2_path)?;
+    create_test_repo(&repo3_path)?;
+    let first_commit = repo1.head()?.peel_to_commit()?.id();
+
+    let repos_file = main_repo_path.join("test.repos");
+    let repos_arg = repos_file.to_str().unwrap();
+    write_repos_file(
+        &repos_file,
+        &[("repo1", &repo1_path), ("repo2", &repo2_path)],
+    )?;
+    assert!(run_vcs2git(&main_repo_path, &[repos_arg, "src"])?
+        .status
+        .success());
+    commit(&Repository::open(&main_repo_path)?, "Add submodules")?;
+    let gitmodules = fs::read(main_repo_path.join(".gitmodules"))?;
+
+    // Update repo1, remove repo2 and add repo3
+    let second_commit = commit(&repo1, "Second commit")?;
+    write_repos_file(
+        &repos_file,
+        &[("repo1", &repo1_path), ("repo3", &repo3_path)],
+    )?;
+    let output = run_vcs2git(&main_repo_path, &[repos_arg, "src", "--sync-selection"])?;
+    assert!(output.status.success());
+    let main_repo = Repository::open(&main_repo_path)?;
+    assert_eq!(
+        main_repo.find_submodule("src/test/repo1")?.workdir_id(),
+        Some(second_commit)
+    );
+    assert!(!main_repo_path.join("src/test/repo2").exists());
+
+    let output = run_vcs2git(&main_repo_path, &["undo"])?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let main_repo = Repository::open(&main_repo_path)?;
+    assert_eq!(fs::read(main_repo_path.join(".gitmodules"))?, gitmodules);
+    assert_eq!(
+        main_repo.find_submodule("src/test/repo1")?.workdir_id(),
+        Some(first_commit)
+    );
+    assert!(main_repo.find_submodule("src/test/repo2")?.open().is_ok());
+    assert!(main_repo.find_submodule("src/test/repo3").is_err());
+    assert!(!main_repo_path.join("src/test/repo3").exists());
+    assert!(!main_repo_path.join(".git/modules/src/test/repo3").exists());
+    let statuses = main_repo.statuses(None)?;
+    assert!(
+        statuses
+            .iter()
+            .all(|entry| entry.path() == Some("test.repos")),
+        "unexpected changes after undo"
+    );
+
+    // There is nothing left to undo
+    let output = run_vcs2git(&main_repo_path, &["undo"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("There is no run to undo"),
+        "stderr: {stderr}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_undo_refuses_after_commit() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo1_path = temp_dir.path().join("repo1");
+    create_test_repo(&repo1_path)?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    write_repos_file(&repos_file, &[("repo1", &repo1_path)])?;
+    let output = run_vcs2git(&main_repo_path, &[repos_file.to_str().unwrap(), "src"])?;
+    assert!(output.status.success());
+    commit(&Repository::open(&main_repo_path)?, "Add submodule")?;
+
+    let output = run_vcs2git(&main_repo_path, &["undo"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--force"), "stderr: {stderr}");
+    assert!(main_repo_path.join("src/test/repo1").exists());
+
+    // The removal is left staged on top of the commit
+    let output = run_vcs2git(&main_repo_path, &["undo", "--force"])?;
+    assert!(output.status.success());
+    let main_repo = Repository::open(&main_repo_path)?;
+    let index = main_repo.index()?;
+    assert!(index.get_path(Path::new("src/test/repo1"), 0).is_none());
+    assert!(index.get_path(Path::new(".gitmodules"), 0).is_none());
+    assert!(!main_repo_path.join("src/test/repo1").exists());
+    assert!(!main_repo_path.join(".gitmodules").exists());
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to commit the index on top of HEAD to a reference
+fn commit_to(repo: &Repository, reference: &str, message: &str) -> Result<git2::Oid> {
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let parent = repo.head()?.peel_to_commit()?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    Ok(repo.commit(Some(reference), &sig, &sig, message, &tree, &[&parent])?)
+}
+
+/// Helper to run vcs2git in the main repository
+fn run_vcs2git(main_repo_path: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(main_repo_path)
+        .args(args)
+        .output()?)
+}
+
+/// Helper to write a repos file listing the given repositories
+fn write_repos_file(path: &Path, repos: &[(&str, &Path, &str)]) -> Result<()> {
+    let mut content = "repositories:\n".to_string();
+    for (name, url, version) in repos {
+        content.push_str(&format!(
+            "  test/{name}:\n    type: git\n    url: file://{}\n    version: {version}\n",
+            url.display()
+        ));
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[test]
+fn test_refuses_to_remove_unpushed_branch() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let repo1_path = temp_dir.path().join("repo1");
+    let repo2_path = temp_dir.path().join("repo2");
+    create_test_repo(&repo1_path)?;
+    create_test_repo(&repo2_path)?;
+
+    let repos_file = main_repo_path.join("test.repos");
+    let repos_arg = repos_file.to_str().unwrap();
+    write_repos_file(
+        &repos_file,
+        &[
+            ("repo1", &repo1_path, "main"),
+            ("repo2", &repo2_path, "main"),
+        ],
+    )?;
+    assert!(run_vcs2git(&main_repo_path, &[repos_arg, "src"])?
+        .status
+        .success());
+    commit_to(
+        &Repository::open(&main_repo_path)?,
+        "HEAD",
+        "Add submodules",
+    )?;
+
+    // A local branch with a commit that exists nowhere else
+    let sub_repo = Repository::open(main_repo_path.join("src/test/repo2"))?;
+    commit_to(&sub_repo, "refs/heads/work", "Unpushed work")?;
+
+    write_repos_file(&repos_file, &[("repo1", &repo1_path, "main")])?;
+    let output = run_vcs2git(&main_repo_path, &[repos_arg, "src", "--sync-selection"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("src/test/repo2: branch 'work' with 1 unpushed commits"),
+        "stderr: {stderr}"
+    );
+    assert!(stderr.contains("--force-remove"), "stderr: {stderr}");
+    assert!(main_repo_path.join("src/test/repo2").exists());
+
+    let output = run_vcs2git(
+        &main_repo_path,
+        &[repos_arg, "src", "--sync-selection", "--force-remove"],
+    )?;
+    assert!(output.status.success());
+    assert!(!main_repo_path.join("src/test/repo2").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_refuses_to_update_away_from_unpushed_commit() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let main_repo_path = temp_dir.path().join("main");
+    fs::create_dir(&main_repo_path)?;
+    create_test_repo(&main_repo_path)?;
+
+    let sub_path = temp_dir.path().join("sub");
+    let sub_origin = create_test_repo(&sub_path)?;
+    let first_commit = sub_origin.head()?.peel_to_commit()?.id().to_string();
+    let second_commit = commit_to(&sub_origin, "HEAD", "Second commit")?.to_string();
+
+    // Pinned to a commit hash, which is fetched without a branch
+    let repos_file = main_repo_path.join("test.repos");
+    let repos_arg = repos_file.to_str().unwrap();
+    write_repos_file(&repos_file, &[("sub", &sub_path, &first_commit)])?;
+    assert!(run_vcs2git(&main_repo_path, &[repos_arg, "src"])?
+        .status
+        .success());
+
+    // A commit on the detached HEAD of the submodule
+    let sub_repo = Repository::open(main_repo_path.join("src/test/sub"))?;
+    commit_to(&sub_repo, "HEAD", "Unpushed work")?;
+    let main_repo = Repository::open(&main_repo_path)?;
+    let mut index = main_repo.index()?;
+    index.add_path(Path::new("src/test/sub"))?;
+    index.write()?;
+    commit_to(&main_repo, "HEAD", "Add submodule")?;
+
+    write_repos_file(&repos_file, &[("sub", &sub_path, &second_commit)])?;
+    let output = run_vcs2git(&main_repo_path, &[repos_arg, "src"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("src/test/sub: 1 unpushed commits at HEAD"),
+        "stderr: {stderr}"
+    );
+
+    let output = run_vcs2git(&main_repo_path, &[repos_arg, "src", "--force-remove"])?;
+    assert!(output.status.success());
+    let sub_repo = Repository::open(main_repo_path.join("src/test/sub"))?;
+    assert_eq!(
+        sub_repo.head()?.target().map(|id| id.to_string()),
+        Some(second_commit)
+    );
+
+    Ok(())
+}
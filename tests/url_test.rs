@@ -0,0 +1,211 @@
+use color_eyre::Result;
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Helper to create a test Git repository with initial commit
+fn create_test_repo(path: &Path) -> Result<Repository> {
+    let repo = Repository::init(path)?;
+
+    // Create initial commit
+    let sig = git2::Signature::now("Test User", "test@example.com")?;
+    let tree_id = {
+        let mut index = repo.index()?;
+        index.write_tree()?
+    };
+    let tree = repo.find_tree(tree_id)?;
+
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])?;
+
+    drop(tree);
+    Ok(repo)
+}
+
+/// Helper to create a superproject and a repository under `mirror/`
+fn setup_mirror(temp_dir: &TempDir) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let main_repo_path = temp_dir.path().join("main");
+    let mirror_path = temp_dir.path().join("mirror");
+    fs::create_dir(&main_repo_path)?;
+    fs::create_dir_all(mirror_path.join("org/sub"))?;
+    create_test_repo(&main_repo_path)?;
+    create_test_repo(&mirror_path.join("org/sub"))?;
+    Ok((main_repo_path, mirror_path))
+}
+
+#[test]
+fn test_rewrite_url_records_original() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, mirror_path) = setup_mirror(&temp_dir)?;
+
+    // The upstream URL does not exist; only the mirror does
+    let repos_content = r#"repositories:
+  test/sub:
+    type: git
+    url: https://upstream.invalid/org/sub.git
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--rewrite-url-regex",
+            &format!(
+                "^https://upstream\\.invalid/(.*)\\.git$=file://{}/$1",
+                mirror_path.display()
+            ),
+        ])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(gitmodules.contains("url = https://upstream.invalid/org/sub.git"));
+    assert!(main_repo_path.join("src/test/sub/.git").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrite_url_from_config() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, mirror_path) = setup_mirror(&temp_dir)?;
+
+    let repos_content = r#"repositories:
+  test/sub:
+    type: git
+    url: https://upstream.invalid/org/sub
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    fs::write(
+        main_repo_path.join(".vcs2git.toml"),
+        format!(
+            r#"record_url = "rewritten"
+
+[[url_rewrites]]
+instead_of = "https://upstream.invalid/"
+base = "file://{}/"
+"#,
+            mirror_path.display()
+        ),
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(
+        gitmodules.contains(&format!("url = file://{}/org/sub", mirror_path.display())),
+        "Unexpected .gitmodules:\n{gitmodules}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_url_style_normalizes_gitmodules() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, mirror_path) = setup_mirror(&temp_dir)?;
+
+    let repos_content = r#"repositories:
+  test/sub:
+    type: git
+    url: git@github.com:org/sub.git
+    version: main
+"#;
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    // Rewrites apply after the style conversion
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([
+            repos_file.to_str().unwrap(),
+            "src",
+            "--url-style",
+            "https",
+            "--rewrite-url-regex",
+            &format!(
+                "^https://github\\.com/(.*)\\.git$=file://{}/$1",
+                mirror_path.display()
+            ),
+        ])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(
+        gitmodules.contains("url = https://github.com/org/sub.git"),
+        "Unexpected .gitmodules:\n{gitmodules}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fallback_url_used_when_primary_fails() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (main_repo_path, mirror_path) = setup_mirror(&temp_dir)?;
+
+    let repos_content = format!(
+        r#"repositories:
+  test/sub:
+    type: git
+    url: file:///nonexistent/org/sub
+    version: main
+    fallback_urls:
+      - file:///nonexistent/second/sub
+      - file://{}/org/sub
+"#,
+        mirror_path.display()
+    );
+    let repos_file = temp_dir.path().join("test.repos");
+    fs::write(&repos_file, repos_content)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vcs2git"))
+        .current_dir(&main_repo_path)
+        .args([repos_file.to_str().unwrap(), "src"])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The primary URL is recorded and the fallback is reported
+    let gitmodules = fs::read_to_string(main_repo_path.join(".gitmodules"))?;
+    assert!(gitmodules.contains("url = file:///nonexistent/org/sub"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("fetched from fallback URLs"),
+        "Unexpected output: {stdout}"
+    );
+
+    Ok(())
+}